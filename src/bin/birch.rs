@@ -1,23 +1,48 @@
 use std::sync::mpsc;
 
-use chess::Color;
-use chess::GameResult;
+use chess::{Color, GameResult, ALL_COLORS};
 use vampirc_uci::UciMessage;
 
 use bebchess::birch::birch_game::BirchGame;
 use bebchess::birch::players::Players;
+use bebchess::syzygy::{Tablebases, Wdl};
+use bebchess::uci::client::is_best_move;
 
 const GERALD_BASE: &str = "/Users/barneyb/IdeaProjects/Senior-Project-Chess-AI/base_engine";
 const GERALD_EVAL: &str = "/Users/barneyb/IdeaProjects/Senior-Project-Chess-AI/eval_engine";
 const GERALD_SEARCH: &str = "/Users/barneyb/IdeaProjects/Senior-Project-Chess-AI/search_engine";
 const GERALD_TUNED: &str = "/Users/barneyb/IdeaProjects/Senior-Project-Chess-AI/tuned_engine";
+/// Or [bebchess::engines::BUILTIN_RACHEL], to play her without building
+/// her binary first. Any engine may also be a `host:port` to connect to, such
+/// as `rachel --listen 0.0.0.0:4000` on another box, or the command of a CECP
+/// engine after [bebchess::engines::CECP_PREFIX].
 const RACHEL: &str = "/Users/barneyb/IdeaProjects/bebchess/target/debug/rachel";
 
+/// Options to set on each engine, e.g. `&[("NullMove", "false")]`, to pit
+/// otherwise-identical builds against one another.
+const WHITE_OPTIONS: &[(&str, &str)] = &[];
+const BLACK_OPTIONS: &[(&str, &str)] = &[];
+/// Whether to let each engine think on its opponent's time.
+const WHITE_PONDER: bool = false;
+const BLACK_PONDER: bool = false;
+
+/// Node counts each engine's `bench` must report (with its options set), to be
+/// sure the intended build is playing. Rachel prints hers with `rachel bench`.
+const WHITE_BENCH: Option<u64> = None;
+const BLACK_BENCH: Option<u64> = None;
+/// Directories of Syzygy tables, to adjudicate games once they reach a position
+/// in them, e.g. `Some("/path/to/syzygy")`.
+const SYZYGY_PATH: Option<&str> = None;
+/// A directory to record each engine's session to, to replay with
+/// `uci-replay`, e.g. `Some("/tmp/birch")`.
+const RECORD_DIR: Option<&str> = None;
+
 /// BIRCH: Barney's Incredibly Ridiculous Chess Harness
 fn main() {
     println!("Hello, from BIRCH!");
     let (tx, rx) = mpsc::channel();
-    let mut players = Players::new(tx, GERALD_TUNED, GERALD_BASE);
+    let mut players = Players::new(GERALD_TUNED, GERALD_BASE, RECORD_DIR);
+    let tablebases = SYZYGY_PATH.map(|p| Tablebases::open(p).expect("Syzygy tables"));
     let mut game = Box::new(BirchGame::new());
     // use std::str::FromStr;
     // let fen = "8/6n1/8/3k4/1K6/8/8/8 w - - 0 79";
@@ -27,29 +52,37 @@ fn main() {
 
     // todo: need to handle an engine crash
     let mut pgn = String::new();
-    let mut is_ready = [false; 2];
-    'init_loop: for (c, msg) in rx.iter() {
-        match &msg {
-            UciMessage::Id {
-                name: Some(name), ..
-            } => pgn += &format!("[{c:?} \"{name}\"]\n"),
-            UciMessage::Id { .. } | UciMessage::Option(_) => {}
-            UciMessage::UciOk => {
-                // set options
-                players.send(c, UciMessage::IsReady);
+    for c in ALL_COLORS {
+        let (options, ponder, bench) = match c {
+            Color::White => (WHITE_OPTIONS, WHITE_PONDER, WHITE_BENCH),
+            Color::Black => (BLACK_OPTIONS, BLACK_PONDER, BLACK_BENCH),
+        };
+        let player = players.player(c);
+        pgn += &format!("[{c:?} \"{}\"]\n", player.name());
+        for (name, value) in options {
+            if let Err(e) = player.set_option(name, value) {
+                panic!("{c:?}: {e}");
             }
-            UciMessage::ReadyOk => {
-                players.send(c, UciMessage::UciNewGame);
-                is_ready[c.to_index()] = true;
-                if is_ready[(!c).to_index()] {
-                    break 'init_loop;
-                }
-            }
-            um => {
-                eprintln!("Received unexpected {}", um)
+        }
+        if let Some(n) = bench {
+            let nodes = player
+                .bench()
+                .unwrap_or_else(|e| panic!("{c:?} didn't bench: {e}"));
+            if nodes != n {
+                panic!("{c:?} benched {nodes} nodes, not {n}");
             }
+            println!("[{c:?} bench: {nodes} nodes]");
+        }
+        if ponder {
+            players.enable_ponder(c);
         }
+        let player = players.player(c);
+        player
+            .is_ready()
+            .unwrap_or_else(|e| panic!("{c:?} isn't ready: {e}"));
+        player.send(UciMessage::UciNewGame);
     }
+    players.start(tx);
 
     // lets go!
     players.next_turn(&game);
@@ -57,8 +90,13 @@ fn main() {
     'message_loop: for (c, msg) in rx.iter() {
         match &msg {
             UciMessage::Info(_) => {}
-            UciMessage::BestMove { best_move: m, .. } => {
-                if c == game.side_to_move() {
+            UciMessage::BestMove {
+                best_move: m,
+                ponder,
+            } => {
+                if !players.accept_best_move(c) {
+                    // from a ponder search which guessed wrong
+                } else if c == game.side_to_move() {
                     if game.make_move(*m) {
                         if c == Color::White {
                             pgn += &format!("{}. {m}", game.get_full_move_counter());
@@ -71,11 +109,24 @@ fn main() {
                         panic!("{:?} made illegal '{m}' from '{}'", c, game)
                     }
                     game.declare_draw_if_appropriate();
+                    let wdl = tablebases
+                        .as_ref()
+                        .and_then(|tb| tb.probe_wdl(&game.current_position()));
+                    if let (None, Some(wdl)) = (game.result(), wdl) {
+                        let stm = game.side_to_move();
+                        game.adjudicate(match wdl {
+                            Wdl::Win => Some(stm),
+                            Wdl::Loss => Some(!stm),
+                            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => None,
+                        });
+                        println!("\n{{ adjudicated by tablebase: {wdl:?} for {stm:?} }}");
+                    }
                     if let Some(_) = game.result() {
                         println!(); // if white plays last, terminate the log
                         break 'message_loop;
                     } else {
                         players.next_turn(&game);
+                        players.ponder(&game, *ponder);
                     }
                 } else {
                     eprintln!(
@@ -88,6 +139,13 @@ fn main() {
             UciMessage::Registration(_) | UciMessage::CopyProtection(_) => {
                 eprintln!("Received unexpected {}", msg)
             }
+            UciMessage::Unknown(..) if is_best_move(&msg) => {
+                // the null move: from a ponder search which was stopped, as a
+                // game with no moves left is already over
+                if players.accept_best_move(c) {
+                    eprintln!("{c:?} unexpectedly sent '{msg}'");
+                }
+            }
             UciMessage::Unknown(_, Some(e)) => {
                 eprintln!("Received unknown {msg}: {e}")
             }
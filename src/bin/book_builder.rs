@@ -0,0 +1,78 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::process::exit;
+use std::str::FromStr;
+
+use bebchess::pgn::parse_games;
+use bebchess::polyglot::builder::BookBuilder;
+
+const USAGE: &str = "Usage: book-builder [--max-ply N] [--min-games N] [--min-score F] \
+<book.bin> <games.pgn>...";
+
+/// Build a Polyglot opening book from PGN game collections.
+fn main() {
+    let mut builder = BookBuilder::default();
+    let mut paths = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-ply" => builder.max_ply = flag_value(&arg, args.next()),
+            "--min-games" => builder.min_games = flag_value(&arg, args.next()),
+            "--min-score" => builder.min_score = flag_value(&arg, args.next()),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() < 2 {
+        eprintln!("{USAGE}");
+        exit(2);
+    }
+    let book_path = paths.remove(0);
+    let (mut used, mut skipped) = (0, 0);
+    for path in paths {
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(e) => {
+                eprintln!("Can't read '{path}': {e}");
+                exit(1);
+            }
+        };
+        for (i, game) in parse_games(&text).into_iter().enumerate() {
+            match game {
+                Ok(g) if builder.add_game(&g) => used += 1,
+                Ok(_) => skipped += 1,
+                Err(e) => {
+                    eprintln!("{path}: game {}: {e}", i + 1);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+    let written = File::create(&book_path)
+        .map(BufWriter::new)
+        .and_then(|mut out| {
+            let n = builder.write(&mut out)?;
+            out.flush()?;
+            Ok(n)
+        });
+    match written {
+        Ok(n) => println!("Wrote {n} entries from {used} games ({skipped} skipped) to {book_path}"),
+        Err(e) => {
+            eprintln!("Can't write '{book_path}': {e}");
+            exit(1);
+        }
+    }
+}
+
+fn flag_value<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.as_deref().map(T::from_str) {
+        Some(Ok(v)) => v,
+        _ => {
+            eprintln!("{flag} needs a valid value, not {value:?}\n{USAGE}");
+            exit(2);
+        }
+    }
+}
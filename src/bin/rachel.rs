@@ -1,55 +1,52 @@
-use std::io;
-use std::pin::Pin;
-
+use async_std::net::TcpListener;
 use async_std::task::block_on;
-use futures::{join, SinkExt, Stream, StreamExt};
-use vampirc_uci::UciMessage;
 
-use bebchess::uci::*;
+use bebchess::rachel::bench::{bench, BENCH_DEPTH};
+use bebchess::rachel::engine::Rachel;
+use bebchess::rachel::search::SearchParams;
+use bebchess::uci::duplex::Pipe;
+use bebchess::uci::engine::{run_engine, run_std_engine};
+use bebchess::uci::record::{Direction, Recorder};
+use bebchess::uci::tcp::serve;
+use bebchess::uci::{stdin_msg_stream, stdout_msg_sink, LoopEnd};
 
 /// RACHEL: Really Awful CHess Engine for Learning
 fn main() {
-    let (itx, irx) = new_try_channel();
-    let (mut otx, orx) = new_channel();
-    block_on(async {
-        join!(
-            run_std_loops(itx, orx),
-            process_messages(Box::pin(irx), &mut otx)
-        );
-    });
-}
-
-async fn process_messages(
-    // engine: Arc<Engine>,
-    mut msg_stream: Pin<Box<impl Stream<Item = io::Result<UciMessage>>>>,
-    // msg_handler: &dyn MsgHandler,
-    msg_sender: &mut UciSender,
-) {
-    while let Some(msg_r) = msg_stream.next().await {
-        if let Ok(msg) = msg_r {
-            eprintln!("[RACHEL] < {msg}");
-            match msg {
-                UciMessage::Quit => break,
-                UciMessage::Uci => {
-                    println!("I AM A GOAT"); //todo
-                    msg_sender
-                        .send(UciMessage::id_name("Rachel"))
-                        .await
-                        .expect("Failed to send name");
-                    msg_sender
-                        .send(UciMessage::id_author("Barney Boisvert"))
-                        .await
-                        .expect("Failed to send author");
-                    msg_sender
-                        .send(UciMessage::UciOk)
-                        .await
-                        .expect("Failed to send OK");
-                }
-                _ => {}
-            };
-            // msg_handler.handle_msg(engine.as_ref(), &msg, msg_sender);
-        } else {
-            eprintln!("[RACHEL] ! {}", msg_r.err().unwrap());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "bench") {
+        let depth = args
+            .get(1)
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(BENCH_DEPTH);
+        for line in bench(depth, &SearchParams::default()).report() {
+            println!("{line}");
+        }
+        return;
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    // a session per connection, for playing from elsewhere
+    if let ["--listen", addr] = args[..] {
+        let listener = block_on(TcpListener::bind(addr)).expect("Failed to listen");
+        block_on(serve(listener, Rachel::new)).expect("Failed to serve");
+        return;
+    }
+    let end = if let ["--record", path] = args[..] {
+        // to replay with uci-replay
+        let recorder = Recorder::create(path).expect("Failed to record");
+        let pipe = Pipe {
+            stream: stdin_msg_stream(),
+            sink: stdout_msg_sink(),
+        };
+        let pipe = recorder.tap(pipe, Direction::FromEngine);
+        block_on(run_engine(&mut Rachel::new(), pipe.stream, pipe.sink))
+    } else {
+        block_on(run_std_engine(&mut Rachel::new()))
+    };
+    match end {
+        LoopEnd::Quit | LoopEnd::Eof => {}
+        end => {
+            eprintln!("[RACHEL] ! session ended: {end:?}");
+            std::process::exit(1);
         }
     }
 }
@@ -0,0 +1,55 @@
+use std::process::exit;
+use std::time::Duration;
+
+use async_std::task::block_on;
+
+use bebchess::engines::open;
+use bebchess::uci::check::{Check, Report, Timeouts, ALL_CHECKS};
+
+const USAGE: &str = "Usage: uci-check <engine> [check...]";
+
+/// Check a UCI engine (a command, a `host:port`, or `builtin:rachel`) does as
+/// the protocol says, reporting each check as it goes. Exits non-zero if any
+/// failed.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((spec, names)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        let names: Vec<_> = ALL_CHECKS.iter().map(Check::name).collect();
+        eprintln!("Checks: {}", names.join(", "));
+        exit(2);
+    };
+    let checks: Vec<Check> = if names.is_empty() {
+        ALL_CHECKS.to_vec()
+    } else {
+        names
+            .iter()
+            .map(|n| {
+                n.parse().unwrap_or_else(|e| {
+                    eprintln!("{e}\n{USAGE}");
+                    exit(2)
+                })
+            })
+            .collect()
+    };
+    let timeouts = Timeouts::default();
+    let mut failed = 0;
+    for &check in &checks {
+        let report = match open(spec) {
+            Ok(client) => block_on(check.run(client, &timeouts)),
+            Err(e) => Report {
+                check,
+                result: Err(format!("can't start {spec}: {e}")),
+                elapsed: Duration::ZERO,
+            },
+        };
+        println!("{report}");
+        if report.result.is_err() {
+            failed += 1;
+        }
+    }
+    println!("{} of {} passed", checks.len() - failed, checks.len());
+    if failed > 0 {
+        exit(1);
+    }
+}
@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+use std::time::Duration;
+
+use async_std::task::block_on;
+
+use bebchess::engines::open;
+use bebchess::uci::record::{read_session, replay};
+
+const USAGE: &str = "Usage: uci-replay <session> <engine>";
+
+/// How long the engine has to say each thing it said before.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the engine has to exit once the session's over, before it's killed.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Replay a recorded session's GUI messages to a UCI engine (a command, a
+/// `host:port`, or `builtin:rachel`), reporting where it says other than it
+/// did then. Exits non-zero if anywhere.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [path, spec] = &args[..] else {
+        eprintln!("{USAGE}");
+        exit(2);
+    };
+    let file = File::open(path).unwrap_or_else(|e| panic!("Failed to open {path}: {e}"));
+    let session = read_session(BufReader::new(file)).unwrap_or_else(|e| {
+        eprintln!("{path}: {e}");
+        exit(2)
+    });
+    let mut client = open(spec).unwrap_or_else(|e| panic!("Failed to start {spec}: {e}"));
+    let divergences = block_on(async {
+        let divergences = replay(&session, &mut client, REPLY_TIMEOUT).await;
+        if let Err(e) = client.close(QUIT_TIMEOUT).await {
+            eprintln!("[REPLAY] ! {e}");
+        }
+        divergences
+    });
+    for divergence in &divergences {
+        println!("{divergence}");
+    }
+    println!(
+        "{} divergences in {} messages",
+        divergences.len(),
+        session.len()
+    );
+    if !divergences.is_empty() {
+        exit(1);
+    }
+}
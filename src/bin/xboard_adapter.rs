@@ -0,0 +1,50 @@
+use std::process::exit;
+use std::time::Duration;
+
+use async_std::task::block_on;
+
+use bebchess::cecp::adapter::Adapter;
+use bebchess::cecp::{bridge, stdin_stream, stdout_sink};
+use bebchess::engines::open;
+
+const USAGE: &str = "Usage: xboard-adapter <engine>";
+
+/// How long the engine has to answer `uci`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the engine has to exit after `quit`, before it's killed.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Play a UCI engine (a command, a `host:port`, or `builtin:rachel`) from a
+/// GUI which only speaks CECP (XBoard), on stdin and stdout.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [spec] = &args[..] else {
+        eprintln!("{USAGE}");
+        exit(2);
+    };
+    let mut client = open(spec).unwrap_or_else(|e| panic!("Failed to start {spec}: {e}"));
+    block_on(async {
+        let handshake = client
+            .uci(HANDSHAKE_TIMEOUT)
+            .await
+            .unwrap_or_else(|e| panic!("[ADAPTER] ! {e}"));
+        let mut adapter = Adapter::new(&handshake);
+        let mut engine_sink = client.take_sink();
+        let engine_stream = client.take_stream();
+        let mut gui_sink = stdout_sink();
+        let bridged = bridge(
+            &mut adapter,
+            stdin_stream(),
+            &mut gui_sink,
+            engine_stream,
+            &mut engine_sink,
+        );
+        if let Err(e) = bridged.await {
+            eprintln!("[ADAPTER] ! {e}");
+        }
+        drop(engine_sink);
+        if let Err(e) = client.close(QUIT_TIMEOUT).await {
+            eprintln!("[ADAPTER] ! {e}");
+        }
+    });
+}
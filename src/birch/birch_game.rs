@@ -46,6 +46,18 @@ impl BirchGame {
         self.game.declare_draw()
     }
 
+    /// End the game by decree, rather than by play: `winner` wins by the other
+    /// side resigning, or with no winner, it's a draw by agreement.
+    pub fn adjudicate(&mut self, winner: Option<Color>) -> bool {
+        match winner {
+            Some(c) => self.game.resign(!c),
+            None => {
+                let side = self.game.side_to_move();
+                self.game.offer_draw(side) && self.game.accept_draw()
+            }
+        }
+    }
+
     /// Override [Game::get_full_move_counter] to supply the pre-computed value.
     pub fn get_full_move_counter(&self) -> usize {
         assert_eq!(self.full_move_counter, self.game.get_full_move_counter());
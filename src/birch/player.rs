@@ -1,18 +1,45 @@
 use std::io;
-use std::process::{Command, ExitStatus};
+use std::process::ExitStatus;
 use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
-use std::thread::sleep;
+use std::thread;
 use std::time::Duration;
 
-use chess::Color;
-use interactive_process::InteractiveProcess;
-use vampirc_uci::{parse_with_unknown, Serializable, UciMessage};
+use async_std::task::block_on;
+use chess::{ChessMove, Color};
+use futures::StreamExt;
+use vampirc_uci::UciMessage;
+
+use crate::engines::open;
+use crate::rachel::bench::parse_nodes;
+use crate::uci::client::{Client, Handshake};
+use crate::uci::record::Recorder;
+
+/// How long an engine has to answer `uci` or `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an engine has to run its `bench`.
+const BENCH_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long an engine has to exit after `quit`, before it's killed.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The node count from a line of an engine's bench report.
+fn nodes(msg: &UciMessage) -> Option<u64> {
+    match msg {
+        UciMessage::Unknown(text, _) => parse_nodes(text),
+        _ => None,
+    }
+}
 
 pub struct Player {
     color: Color,
-    proc: InteractiveProcess,
+    client: Client,
+    handshake: Handshake,
     sent_quit: bool,
+    /// Whether to let the engine think on its opponent's time.
+    ponder: bool,
+    /// The move the engine is pondering a reply to, if it is pondering.
+    pondering: Option<ChessMove>,
+    /// Best moves still to come from stopped ponder searches, to be ignored.
+    stale_best_moves: usize,
 }
 
 fn label(color: Color) -> &'static str {
@@ -23,28 +50,136 @@ fn label(color: Color) -> &'static str {
 }
 
 impl Player {
-    pub fn new(
-        color: Color,
-        cmd_str: &str,
-        sender: Arc<Mutex<Sender<(Color, UciMessage)>>>,
-    ) -> Player {
-        let mut cmd = Command::new(cmd_str);
-        let proc = InteractiveProcess::new(&mut cmd, move |r| match r {
-            Ok(line) => {
-                for msg in parse_with_unknown(&line) {
-                    // println!("[{}] < \t{msg}", label(color));
-                    sender.lock().unwrap().send((color, msg)).unwrap();
-                }
-            }
-            Err(e) => {
-                println!("[{}] ! {e}", label(color));
-            }
-        })
-        .unwrap();
+    /// Start the engine `spec` (see [open]) and shake hands with it, having
+    /// `recorder` record the session, if given. Its messages are held until
+    /// [Player::start].
+    pub fn new(color: Color, spec: &str, recorder: Option<&Recorder>) -> Player {
+        let mut client = open(spec).unwrap_or_else(|e| panic!("Failed to start {spec}: {e}"));
+        if let Some(recorder) = recorder {
+            client.record(recorder);
+        }
+        let handshake = block_on(client.uci(HANDSHAKE_TIMEOUT))
+            .unwrap_or_else(|e| panic!("[{}] ! {e}", label(color)));
         Player {
             color,
-            proc,
+            client,
+            handshake,
             sent_quit: false,
+            ponder: false,
+            pondering: None,
+            stale_best_moves: 0,
+        }
+    }
+
+    /// The engine's name, or failing that, its color.
+    pub fn name(&self) -> &str {
+        self.handshake.name.as_deref().unwrap_or(label(self.color))
+    }
+
+    /// Set an option the engine declared, checking the value first.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if !self.handshake.options.set(&mut (), name, Some(value))? {
+            return Err(format!("No option {name}"));
+        }
+        self.send(UciMessage::SetOption {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+        });
+        Ok(())
+    }
+
+    /// Have the engine run its `bench`, returning the node count it reports.
+    pub fn bench(&mut self) -> io::Result<u64> {
+        self.send(UciMessage::Unknown("bench".to_string(), None));
+        let msgs = block_on(
+            self.client
+                .recv_until(BENCH_TIMEOUT, |m| nodes(m).is_some()),
+        )?;
+        Ok(msgs
+            .last()
+            .and_then(nodes)
+            .expect("Last should be the node count"))
+    }
+
+    /// Wait for the engine to say it's ready.
+    pub fn is_ready(&mut self) -> io::Result<()> {
+        block_on(self.client.is_ready(HANDSHAKE_TIMEOUT))
+    }
+
+    /// Pass everything the engine sends along to `sender`, from a thread.
+    pub fn start(&mut self, sender: Sender<(Color, UciMessage)>) {
+        let color = self.color;
+        let mut stream = self.client.take_stream();
+        thread::spawn(move || {
+            block_on(async {
+                while let Some(r) = stream.next().await {
+                    match r {
+                        Ok(msg) => {
+                            // println!("[{}] < \t{msg}", label(color));
+                            if sender.send((color, msg)).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => println!("[{}] ! {e}", label(color)),
+                    }
+                }
+            })
+        });
+    }
+
+    pub fn set_ponder(&mut self, ponder: bool) {
+        self.ponder = ponder;
+    }
+
+    /// Start pondering `position` (the engine's own move already made), with
+    /// `expected` as the opponent's reply, if pondering is enabled.
+    pub fn start_pondering(&mut self, position: UciMessage, expected: ChessMove) {
+        if !self.ponder {
+            return;
+        }
+        if let UciMessage::Position {
+            startpos,
+            fen,
+            mut moves,
+        } = position
+        {
+            moves.push(expected);
+            self.send(UciMessage::Position {
+                startpos,
+                fen,
+                moves,
+            });
+            self.send(UciMessage::go_ponder());
+            self.pondering = Some(expected);
+        }
+    }
+
+    /// The opponent played `actual`. Returns whether the engine was pondering
+    /// that very move, and so has been sent `ponderhit` and needs nothing more.
+    /// Any other ponder search is stopped, and its best move ignored.
+    pub fn opponent_moved(&mut self, actual: Option<ChessMove>) -> bool {
+        match self.pondering.take() {
+            Some(expected) if Some(expected) == actual => {
+                self.send(UciMessage::PonderHit);
+                true
+            }
+            Some(_) => {
+                self.send(UciMessage::Stop);
+                self.stale_best_moves += 1;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a `bestmove` just received is for real, rather than the result
+    /// of a ponder search which was stopped.
+    pub fn accept_best_move(&mut self) -> bool {
+        if self.stale_best_moves > 0 {
+            self.stale_best_moves -= 1;
+            false
+        } else {
+            true
         }
     }
 
@@ -52,10 +187,8 @@ impl Player {
         if let UciMessage::Quit = message {
             self.sent_quit = true
         }
-        let msg = message.serialize();
-        let msg = msg.trim();
-        // println!("[{}] > {msg}", label(self.color));
-        if let Err(e) = self.proc.send(msg) {
+        // println!("[{}] > {message}", label(self.color));
+        if let Err(e) = block_on(self.client.send(message)) {
             println!("[{}] ! {e}", label(self.color));
         }
     }
@@ -64,13 +197,7 @@ impl Player {
         // todo: make this Drop
         if !self.sent_quit {
             self.send(UciMessage::Quit);
-            sleep(Duration::from_millis(50));
-        }
-        let mut child = self.proc.close();
-        if let Ok(None) = child.try_wait() {
-            child.kill().expect("Failed to kill child");
-            println!("[{}] ! killed still-running engine", label(self.color));
         }
-        child.try_wait()
+        block_on(self.client.close(QUIT_TIMEOUT))
     }
 }
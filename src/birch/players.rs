@@ -1,11 +1,12 @@
+use std::path::Path;
 use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
 
-use chess::Color;
+use chess::{Action, ChessMove, Color};
 use vampirc_uci::{UciFen, UciMessage};
 
 use crate::birch::birch_game::BirchGame;
 use crate::birch::player::Player;
+use crate::uci::record::Recorder;
 
 pub struct Players {
     white: Box<Player>,
@@ -13,39 +14,89 @@ pub struct Players {
 }
 
 impl Players {
-    pub fn new(msg_sink: Sender<(Color, UciMessage)>, white_cmd: &str, black_cmd: &str) -> Players {
-        let sink = Arc::new(Mutex::new(msg_sink));
-        let mut white = Player::new(Color::White, white_cmd, sink.clone());
-        white.send(UciMessage::Uci);
-        let mut black = Player::new(Color::Black, black_cmd, sink.clone());
-        black.send(UciMessage::Uci);
+    /// Start both engines, and shake hands with them. With a `record_dir`,
+    /// each session's recorded there, as `white.uci` and `black.uci`.
+    pub fn new(white_cmd: &str, black_cmd: &str, record_dir: Option<&str>) -> Players {
+        let recorder = |name: &str| {
+            record_dir.map(|dir| {
+                let path = Path::new(dir).join(name);
+                Recorder::create(&path)
+                    .unwrap_or_else(|e| panic!("Failed to record to {}: {e}", path.display()))
+            })
+        };
+        let (white, black) = (recorder("white.uci"), recorder("black.uci"));
         Players {
-            white: Box::new(white),
-            black: Box::new(black),
+            white: Box::new(Player::new(Color::White, white_cmd, white.as_ref())),
+            black: Box::new(Player::new(Color::Black, black_cmd, black.as_ref())),
         }
     }
 
-    pub fn send(&mut self, color: Color, msg: UciMessage) {
-        (match color {
+    /// Send what both engines say along to `msg_sink`, from here on.
+    pub fn start(&mut self, msg_sink: Sender<(Color, UciMessage)>) {
+        self.white.start(msg_sink.clone());
+        self.black.start(msg_sink);
+    }
+
+    pub fn player(&mut self, color: Color) -> &mut Player {
+        match color {
             Color::White => &mut self.white,
             Color::Black => &mut self.black,
-        })
-        .send(msg);
+        }
+    }
+
+    pub fn send(&mut self, color: Color, msg: UciMessage) {
+        self.player(color).send(msg);
+    }
+
+    /// Enable pondering for `color`'s engine, which must also be told so.
+    pub fn enable_ponder(&mut self, color: Color) {
+        let player = self.player(color);
+        player.set_ponder(true);
+        player.send(UciMessage::SetOption {
+            name: "Ponder".to_string(),
+            value: Some("true".to_string()),
+        });
+    }
+
+    /// Whether `color`'s `bestmove` should be played, rather than ignored as
+    /// the leftovers of a stopped ponder search.
+    pub fn accept_best_move(&mut self, color: Color) -> bool {
+        self.player(color).accept_best_move()
+    }
+
+    fn position(game: &BirchGame) -> UciMessage {
+        UciMessage::Position {
+            startpos: false,
+            fen: Some(UciFen(game.current_position().to_string())),
+            moves: vec![],
+        }
     }
 
     pub fn next_turn(&mut self, game: &Box<BirchGame>) {
         let c = game.side_to_move();
-        self.send(
-            c,
-            UciMessage::Position {
-                startpos: false,
-                fen: Some(UciFen(game.current_position().to_string())),
-                moves: vec![],
-            },
-        );
+        let last_move = match game.actions().last() {
+            Some(Action::MakeMove(m)) => Some(*m),
+            _ => None,
+        };
+        if self.player(c).opponent_moved(last_move) {
+            // already searching the right position
+            return;
+        }
+        self.send(c, Self::position(game));
         self.send(c, UciMessage::go())
     }
 
+    /// Let the side which just moved think about `expected`, the reply it
+    /// suggested alongside its move, if it's pondering.
+    pub fn ponder(&mut self, game: &Box<BirchGame>, expected: Option<ChessMove>) {
+        let c = !game.side_to_move();
+        if let Some(m) = expected {
+            if game.current_position().legal(m) {
+                self.player(c).start_pondering(Self::position(game), m);
+            }
+        }
+    }
+
     pub fn close(self) {
         self.white.close().unwrap();
         self.black.close().unwrap();
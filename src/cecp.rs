@@ -0,0 +1,707 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_std::future::ready;
+use async_std::io;
+use chess::{Board, ChessMove};
+use futures::future::Either;
+use futures::{stream, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, Sink, SinkExt};
+use futures::{Stream, StreamExt, TryStreamExt};
+use vampirc_uci::UciOptionConfig;
+
+pub mod adapter;
+pub mod driver;
+
+/*
+CECP (a.k.a. the XBoard or WinBoard protocol) is older, and more stateful than
+UCI: the engine keeps the game, and is told each move as it's made, rather than
+the whole position each time. See https://www.gnu.org/software/xboard/engine-intf.html
+for the details. Only protocol version 2 is supported.
+ */
+
+pub type CecpStream = dyn Stream<Item = io::Result<CecpMessage>> + Unpin + Send;
+pub type CecpSink = dyn Sink<CecpMessage, Error = io::Error> + Unpin + Send;
+
+/// Scores at least this big are mates, in as many moves as they're over.
+pub const MATE_SCORE: i32 = 100_000;
+
+/// A line of CECP, in either direction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CecpMessage {
+    // GUI to engine
+    Xboard,
+    Protover(u32),
+    Accepted(String),
+    Rejected(String),
+    New,
+    Variant(String),
+    Quit,
+    Force,
+    Go,
+    PlayOther,
+    /// Moves per session (zero for all of them), base time, and increment.
+    Level {
+        moves: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    /// Exactly this long per move.
+    St(Duration),
+    /// At most this deep.
+    Sd(u8),
+    /// The engine's clock.
+    Time(Duration),
+    /// Its opponent's clock.
+    Otim(Duration),
+    /// `usermove MOVE`, once the engine's asked for it.
+    UserMove(String),
+    /// A move on its own.
+    BareMove(String),
+    /// `?`, to move now.
+    MoveNow,
+    Ping(u32),
+    Draw,
+    Result {
+        result: String,
+        comment: String,
+    },
+    SetBoard(String),
+    Undo,
+    Remove,
+    Hard,
+    Easy,
+    Post,
+    NoPost,
+    Analyze,
+    /// Leave analysis.
+    Exit,
+    Option {
+        name: String,
+        value: Option<String>,
+    },
+    // engine to GUI
+    Feature(Vec<(String, String)>),
+    Move(String),
+    Pong(u32),
+    /// Thinking output, with the score in centipawns (or see [MATE_SCORE]).
+    Thinking {
+        depth: u8,
+        score: i32,
+        time: Duration,
+        nodes: u64,
+        pv: String,
+    },
+    Resign,
+    OfferDraw,
+    /// The engine's claim of a result, e.g. `1-0 {White mates}`.
+    Claim {
+        result: String,
+        comment: String,
+    },
+    Error {
+        kind: String,
+        command: String,
+    },
+    IllegalMove {
+        reason: Option<String>,
+        mv: String,
+    },
+    Unknown(String),
+}
+
+/// CECP's clocks count centiseconds.
+fn from_centis(n: i64) -> Duration {
+    Duration::from_millis((n.max(0) as u64).saturating_mul(10))
+}
+
+fn centis(d: Duration) -> u128 {
+    d.as_millis() / 10
+}
+
+/// `level`'s base time: minutes, or `minutes:seconds`.
+fn parse_base(text: &str) -> Option<Duration> {
+    let (min, sec) = text.split_once(':').unwrap_or((text, "0"));
+    Some(Duration::from_secs(
+        min.parse::<u64>().ok()? * 60 + sec.parse::<u64>().ok()?,
+    ))
+}
+
+fn format_base(d: Duration) -> String {
+    match d.as_secs() {
+        s if s % 60 == 0 => (s / 60).to_string(),
+        s => format!("{}:{:02}", s / 60, s % 60),
+    }
+}
+
+fn parse_secs(text: &str) -> Option<Duration> {
+    text.parse::<f64>()
+        .ok()
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+fn parse_level(args: &str) -> Option<CecpMessage> {
+    match args.split_whitespace().collect::<Vec<_>>()[..] {
+        [moves, base, inc] => Some(CecpMessage::Level {
+            moves: moves.parse().ok()?,
+            base: parse_base(base)?,
+            increment: parse_secs(inc)?,
+        }),
+        _ => None,
+    }
+}
+
+/// A result, and the comment in braces after it.
+fn parse_result(text: &str) -> Option<(String, String)> {
+    let (result, comment) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if !["1-0", "0-1", "1/2-1/2", "*"].contains(&result) {
+        return None;
+    }
+    let comment = comment.trim().trim_start_matches('{').trim_end_matches('}');
+    Some((result.to_string(), comment.to_string()))
+}
+
+/// A move in coordinate notation, which is all a bare move may be, for
+/// telling it from a command.
+fn is_coordinate(text: &str) -> bool {
+    let b = text.as_bytes();
+    let square = |f: u8, r: u8| (b'a'..=b'h').contains(&f) && (b'1'..=b'8').contains(&r);
+    match b.len() {
+        4 => square(b[0], b[1]) && square(b[2], b[3]),
+        5 => square(b[0], b[1]) && square(b[2], b[3]) && b"qrbn".contains(&b[4]),
+        _ => false,
+    }
+}
+
+/// `feature` pairs, whose values may be quoted.
+fn parse_features(mut text: &str) -> Option<Vec<(String, String)>> {
+    let mut features = vec![];
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Some(features);
+        }
+        let (key, rest) = text.split_once('=')?;
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"')?,
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+        features.push((key.to_string(), value.to_string()));
+        text = rest;
+    }
+}
+
+/// `DEPTH SCORE TIME NODES PV`, where some engines tack a character onto the
+/// depth.
+fn parse_thinking(line: &str) -> Option<CecpMessage> {
+    let mut parts = line.splitn(5, char::is_whitespace);
+    let depth = parts
+        .next()?
+        .trim_end_matches(|c: char| !c.is_ascii_digit());
+    Some(CecpMessage::Thinking {
+        depth: depth.parse().ok()?,
+        score: parts.next()?.parse().ok()?,
+        time: from_centis(parts.next()?.parse().ok()?),
+        nodes: parts.next()?.parse().ok()?,
+        pv: parts.next().unwrap_or("").trim().to_string(),
+    })
+}
+
+/// Parse one line of input: `None` if it's blank, and otherwise whatever it
+/// is, with anything unrecognized as [CecpMessage::Unknown].
+pub fn parse_line(line: &str) -> Option<CecpMessage> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let (cmd, rest) = match line.split_once(char::is_whitespace) {
+        Some((cmd, rest)) => (cmd, rest.trim()),
+        None => (line, ""),
+    };
+    let msg = match (cmd, rest) {
+        ("xboard", "") => Some(CecpMessage::Xboard),
+        ("protover", n) => n.parse().ok().map(CecpMessage::Protover),
+        ("accepted", f) => Some(CecpMessage::Accepted(f.to_string())),
+        ("rejected", f) => Some(CecpMessage::Rejected(f.to_string())),
+        ("new", "") => Some(CecpMessage::New),
+        ("variant", v) => Some(CecpMessage::Variant(v.to_string())),
+        ("quit", "") => Some(CecpMessage::Quit),
+        ("force", "") => Some(CecpMessage::Force),
+        ("go", "") => Some(CecpMessage::Go),
+        ("playother", "") => Some(CecpMessage::PlayOther),
+        ("level", args) => parse_level(args),
+        ("st", s) => parse_secs(s).map(CecpMessage::St),
+        ("sd", d) => d.parse().ok().map(CecpMessage::Sd),
+        ("time", n) => n.parse().ok().map(|n| CecpMessage::Time(from_centis(n))),
+        ("otim", n) => n.parse().ok().map(|n| CecpMessage::Otim(from_centis(n))),
+        ("usermove", m) if !m.is_empty() => Some(CecpMessage::UserMove(m.to_string())),
+        (m, "") if is_coordinate(m) => Some(CecpMessage::BareMove(m.to_string())),
+        ("?", "") => Some(CecpMessage::MoveNow),
+        ("ping", n) => n.parse().ok().map(CecpMessage::Ping),
+        ("draw", "") => Some(CecpMessage::Draw),
+        ("result", r) => {
+            parse_result(r).map(|(result, comment)| CecpMessage::Result { result, comment })
+        }
+        ("setboard", fen) if !fen.is_empty() => Some(CecpMessage::SetBoard(fen.to_string())),
+        ("undo", "") => Some(CecpMessage::Undo),
+        ("remove", "") => Some(CecpMessage::Remove),
+        ("hard", "") => Some(CecpMessage::Hard),
+        ("easy", "") => Some(CecpMessage::Easy),
+        ("post", "") => Some(CecpMessage::Post),
+        ("nopost", "") => Some(CecpMessage::NoPost),
+        ("analyze", "") => Some(CecpMessage::Analyze),
+        ("exit", "") => Some(CecpMessage::Exit),
+        ("option", o) if !o.is_empty() => Some(match o.split_once('=') {
+            Some((name, value)) => CecpMessage::Option {
+                name: name.to_string(),
+                value: Some(value.to_string()),
+            },
+            None => CecpMessage::Option {
+                name: o.to_string(),
+                value: None,
+            },
+        }),
+        ("feature", f) => parse_features(f).map(CecpMessage::Feature),
+        ("move", m) if !m.is_empty() => Some(CecpMessage::Move(m.to_string())),
+        ("pong", n) => n.parse().ok().map(CecpMessage::Pong),
+        ("resign", "") => Some(CecpMessage::Resign),
+        ("offer", "draw") => Some(CecpMessage::OfferDraw),
+        ("Illegal", _) => line.strip_prefix("Illegal move").and_then(|rest| {
+            let (reason, mv) = rest.split_once(':')?;
+            let reason = reason.trim().trim_start_matches('(').trim_end_matches(')');
+            Some(CecpMessage::IllegalMove {
+                reason: (!reason.is_empty()).then(|| reason.to_string()),
+                mv: mv.trim().to_string(),
+            })
+        }),
+        ("Error", _) => line.strip_prefix("Error").and_then(|rest| {
+            let (kind, command) = rest.split_once("):")?;
+            Some(CecpMessage::Error {
+                kind: kind.trim().trim_start_matches('(').to_string(),
+                command: command.trim().to_string(),
+            })
+        }),
+        _ => parse_result(line)
+            .map(|(result, comment)| CecpMessage::Claim { result, comment })
+            .or_else(|| parse_thinking(line)),
+    };
+    Some(msg.unwrap_or_else(|| CecpMessage::Unknown(line.to_string())))
+}
+
+impl fmt::Display for CecpMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CecpMessage::Xboard => write!(f, "xboard"),
+            CecpMessage::Protover(n) => write!(f, "protover {n}"),
+            CecpMessage::Accepted(feature) => write!(f, "accepted {feature}"),
+            CecpMessage::Rejected(feature) => write!(f, "rejected {feature}"),
+            CecpMessage::New => write!(f, "new"),
+            CecpMessage::Variant(v) => write!(f, "variant {v}"),
+            CecpMessage::Quit => write!(f, "quit"),
+            CecpMessage::Force => write!(f, "force"),
+            CecpMessage::Go => write!(f, "go"),
+            CecpMessage::PlayOther => write!(f, "playother"),
+            CecpMessage::Level {
+                moves,
+                base,
+                increment,
+            } => {
+                let inc = increment.as_secs_f64();
+                write!(f, "level {moves} {} {inc}", format_base(*base))
+            }
+            CecpMessage::St(d) => write!(f, "st {}", d.as_secs_f64()),
+            CecpMessage::Sd(d) => write!(f, "sd {d}"),
+            CecpMessage::Time(d) => write!(f, "time {}", centis(*d)),
+            CecpMessage::Otim(d) => write!(f, "otim {}", centis(*d)),
+            CecpMessage::UserMove(m) => write!(f, "usermove {m}"),
+            CecpMessage::BareMove(m) => write!(f, "{m}"),
+            CecpMessage::MoveNow => write!(f, "?"),
+            CecpMessage::Ping(n) => write!(f, "ping {n}"),
+            CecpMessage::Draw => write!(f, "draw"),
+            CecpMessage::Result { result, comment } => write!(f, "result {result} {{{comment}}}"),
+            CecpMessage::SetBoard(fen) => write!(f, "setboard {fen}"),
+            CecpMessage::Undo => write!(f, "undo"),
+            CecpMessage::Remove => write!(f, "remove"),
+            CecpMessage::Hard => write!(f, "hard"),
+            CecpMessage::Easy => write!(f, "easy"),
+            CecpMessage::Post => write!(f, "post"),
+            CecpMessage::NoPost => write!(f, "nopost"),
+            CecpMessage::Analyze => write!(f, "analyze"),
+            CecpMessage::Exit => write!(f, "exit"),
+            CecpMessage::Option { name, value } => match value {
+                Some(value) => write!(f, "option {name}={value}"),
+                None => write!(f, "option {name}"),
+            },
+            CecpMessage::Feature(features) => {
+                write!(f, "feature")?;
+                for (key, value) in features {
+                    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+                        write!(f, " {key}={value}")?;
+                    } else {
+                        write!(f, " {key}=\"{value}\"")?;
+                    }
+                }
+                Ok(())
+            }
+            CecpMessage::Move(m) => write!(f, "move {m}"),
+            CecpMessage::Pong(n) => write!(f, "pong {n}"),
+            CecpMessage::Thinking {
+                depth,
+                score,
+                time,
+                nodes,
+                pv,
+            } => write!(f, "{depth} {score} {} {nodes} {pv}", centis(*time)),
+            CecpMessage::Resign => write!(f, "resign"),
+            CecpMessage::OfferDraw => write!(f, "offer draw"),
+            CecpMessage::Claim { result, comment } => write!(f, "{result} {{{comment}}}"),
+            CecpMessage::Error { kind, command } => write!(f, "Error ({kind}): {command}"),
+            CecpMessage::IllegalMove { reason, mv } => match reason {
+                Some(reason) => write!(f, "Illegal move ({reason}): {mv}"),
+                None => write!(f, "Illegal move: {mv}"),
+            },
+            CecpMessage::Unknown(line) => write!(f, "{line}"),
+        }
+    }
+}
+
+/// A legal move on `board`, in coordinate notation (as UCI has it) or SAN.
+pub fn parse_move(board: &Board, text: &str) -> Option<ChessMove> {
+    let text = text.trim_end_matches(['+', '#', '!', '?']);
+    ChessMove::from_str(text)
+        .ok()
+        .or_else(|| ChessMove::from_san(board, text).ok())
+        .filter(|m| board.legal(*m))
+}
+
+/// Option types, as `feature option` declares them.
+const OPTION_TYPES: [&str; 10] = [
+    "-button", "-save", "-reset", "-check", "-string", "-file", "-path", "-spin", "-slider",
+    "-combo",
+];
+/// Between a combo's choices.
+const COMBO_SEPARATOR: &str = " /// ";
+
+/// An engine's option, as the value of CECP's `feature option`.
+pub fn option_feature(config: &UciOptionConfig) -> String {
+    match config {
+        UciOptionConfig::Check { name, default } => {
+            format!("{name} -check {}", u8::from(default.unwrap_or(false)))
+        }
+        UciOptionConfig::Spin {
+            name,
+            default,
+            min,
+            max,
+        } => {
+            let min = min.unwrap_or(i64::MIN);
+            let max = max.unwrap_or(i64::MAX);
+            format!("{name} -spin {} {min} {max}", default.unwrap_or(min.max(0)))
+        }
+        UciOptionConfig::Combo { name, default, var } => {
+            let choices: Vec<String> = var
+                .iter()
+                .map(|v| match default {
+                    Some(d) if d == v => format!("*{v}"),
+                    _ => v.clone(),
+                })
+                .collect();
+            format!("{name} -combo {}", choices.join(COMBO_SEPARATOR))
+        }
+        UciOptionConfig::Button { name } => format!("{name} -button"),
+        UciOptionConfig::String { name, default } => {
+            let default = default.as_deref().filter(|d| *d != "<empty>").unwrap_or("");
+            format!("{name} -string {default}")
+        }
+    }
+}
+
+/// The option a `feature option` declares, as UCI would have it.
+pub fn parse_option_feature(text: &str) -> Option<UciOptionConfig> {
+    let (name, kind, args) = text.match_indices(" -").find_map(|(i, _)| {
+        let rest = &text[i + 1..];
+        OPTION_TYPES.into_iter().find_map(|t| {
+            let args = rest.strip_prefix(t)?;
+            (args.is_empty() || args.starts_with(' ')).then(|| (&text[..i], t, args.trim()))
+        })
+    })?;
+    let name = name.trim().to_string();
+    Some(match kind {
+        "-button" | "-save" | "-reset" => UciOptionConfig::Button { name },
+        "-check" => UciOptionConfig::Check {
+            name,
+            default: Some(args == "1"),
+        },
+        "-string" | "-file" | "-path" => UciOptionConfig::String {
+            name,
+            default: Some(args.to_string()),
+        },
+        "-spin" | "-slider" => match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [default, min, max] => UciOptionConfig::Spin {
+                name,
+                default: default.parse().ok(),
+                min: min.parse().ok(),
+                max: max.parse().ok(),
+            },
+            _ => return None,
+        },
+        _ => {
+            let choices: Vec<&str> = args.split(COMBO_SEPARATOR).map(str::trim).collect();
+            let default = choices.iter().find_map(|c| c.strip_prefix('*'));
+            UciOptionConfig::Combo {
+                name,
+                default: default.or(choices.first().copied()).map(str::to_string),
+                var: choices
+                    .iter()
+                    .map(|c| c.trim_start_matches('*').to_string())
+                    .collect(),
+            }
+        }
+    })
+}
+
+/// Messages from `reader`, a line at a time. Blank lines are skipped, and
+/// bytes which aren't UTF-8 are replaced, rather than ending the stream.
+pub fn from_reader<R>(reader: io::BufReader<R>) -> Box<CecpStream>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    let stream = reader
+        .split(b'\n')
+        .map_ok(|bytes| {
+            let msg = parse_line(&String::from_utf8_lossy(&bytes));
+            stream::iter(msg.map(Ok::<_, io::Error>))
+        })
+        .try_flatten();
+    Box::new(stream)
+}
+
+/// Messages to `writer`, a line at a time.
+pub fn to_writer<W>(writer: W) -> Box<CecpSink>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let sink = writer
+        .into_sink()
+        .with(|msg: CecpMessage| ready(Ok::<_, io::Error>(format!("{msg}\n"))));
+    Box::new(sink)
+}
+
+pub fn stdin_stream() -> Box<CecpStream> {
+    from_reader(io::BufReader::new(io::stdin()))
+}
+
+pub fn stdout_sink() -> Box<CecpSink> {
+    to_writer(io::stdout())
+}
+
+/// What a [Translate] has to send on, each way.
+pub struct Outbox<G, E> {
+    pub to_gui: Vec<G>,
+    pub to_engine: Vec<E>,
+}
+
+impl<G, E> Default for Outbox<G, E> {
+    fn default() -> Self {
+        Outbox {
+            to_gui: Vec::new(),
+            to_engine: Vec::new(),
+        }
+    }
+}
+
+/// Stands between a GUI speaking one protocol and an engine speaking another,
+/// turning what each says into what the other understands.
+pub trait Translate {
+    type Gui;
+    type Engine;
+
+    fn from_gui(&mut self, msg: Self::Gui) -> Outbox<Self::Gui, Self::Engine>;
+
+    fn from_engine(&mut self, msg: Self::Engine) -> Outbox<Self::Gui, Self::Engine>;
+
+    /// Whether the GUI's said to quit, which has been passed along.
+    fn done(&self) -> bool;
+}
+
+/// Pass messages between the GUI and engine through `translator`, until the
+/// GUI quits, or either hangs up.
+pub async fn bridge<T, GS, GK, ES, EK>(
+    translator: &mut T,
+    gui_stream: GS,
+    gui_sink: &mut GK,
+    engine_stream: ES,
+    engine_sink: &mut EK,
+) -> io::Result<()>
+where
+    T: Translate,
+    GS: Stream<Item = io::Result<T::Gui>> + Unpin,
+    GK: Sink<T::Gui, Error = io::Error> + Unpin + ?Sized,
+    ES: Stream<Item = io::Result<T::Engine>> + Unpin,
+    EK: Sink<T::Engine, Error = io::Error> + Unpin + ?Sized,
+{
+    // each ends with None, to notice which hung up
+    let gui = gui_stream
+        .map(Some)
+        .chain(stream::iter([None]))
+        .map(Either::Left);
+    let engine = engine_stream
+        .map(Some)
+        .chain(stream::iter([None]))
+        .map(Either::Right);
+    let mut both = stream::select(gui, engine);
+    while let Some(next) = both.next().await {
+        let out = match next {
+            Either::Left(Some(msg)) => translator.from_gui(msg?),
+            Either::Right(Some(msg)) => translator.from_engine(msg?),
+            Either::Left(None) | Either::Right(None) => break,
+        };
+        for msg in out.to_engine {
+            engine_sink.send(msg).await?;
+        }
+        for msg in out.to_gui {
+            gui_sink.send(msg).await?;
+        }
+        if translator.done() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> CecpMessage {
+        parse_line(line).unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        for line in [
+            "xboard",
+            "protover 2",
+            "new",
+            "level 40 5 0",
+            "level 0 2:30 0.5",
+            "st 10",
+            "sd 6",
+            "time 30000",
+            "otim 29950",
+            "usermove e7e8q",
+            "e2e4",
+            "?",
+            "ping 7",
+            "result 1-0 {White mates}",
+            "setboard 8/8/8/4k3/8/8/4P3/4K3 w - - 0 1",
+            "option Hash=64",
+            "option Clear Hash",
+            "feature myname=\"Rachel 1\" setboard=1 done=1",
+            "move g1f3",
+            "pong 7",
+            "9 -156 1084 48000 Nf3 Nc6 Bb5",
+            "offer draw",
+            "1/2-1/2 {Stalemate}",
+            "Error (unknown command): xyzzy",
+            "Illegal move: e2e5",
+            "Illegal move (in check): e1f1",
+            "telluser hello",
+        ] {
+            assert_eq!(line, parse(line).to_string());
+        }
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(None, parse_line("  "));
+        let level = CecpMessage::Level {
+            moves: 0,
+            base: Duration::from_secs(150),
+            increment: Duration::from_millis(500),
+        };
+        assert_eq!(level, parse("level 0 2:30 0.5"));
+        assert_eq!(
+            CecpMessage::Time(Duration::from_secs(300)),
+            parse("time 30000")
+        );
+        // however long, it doesn't overflow
+        assert_eq!(
+            CecpMessage::Otim(Duration::from_millis(u64::MAX)),
+            parse("otim 9223372036854775807")
+        );
+        let features = vec![
+            ("myname".to_string(), "Rachel 1".to_string()),
+            ("done".to_string(), "1".to_string()),
+        ];
+        assert_eq!(
+            CecpMessage::Feature(features),
+            parse("feature myname=\"Rachel 1\" done=1")
+        );
+        let thinking = CecpMessage::Thinking {
+            depth: 9,
+            score: -156,
+            time: Duration::from_millis(10840),
+            nodes: 48000,
+            pv: "Nf3 Nc6".to_string(),
+        };
+        assert_eq!(thinking, parse("9. -156 1084 48000 Nf3 Nc6"));
+        let claim = CecpMessage::Claim {
+            result: "0-1".to_string(),
+            comment: "Black mates".to_string(),
+        };
+        assert_eq!(claim, parse("0-1 {Black mates}"));
+        // not a coordinate move, so not a move at all
+        assert_eq!(CecpMessage::Unknown("Nf3".to_string()), parse("Nf3"));
+        assert_eq!(
+            CecpMessage::Unknown("protover two".to_string()),
+            parse("protover two")
+        );
+    }
+
+    #[test]
+    fn moves() {
+        let board = Board::default();
+        let nf3 = ChessMove::from_str("g1f3").ok();
+        assert_eq!(nf3, parse_move(&board, "g1f3"));
+        assert_eq!(nf3, parse_move(&board, "Nf3"));
+        assert_eq!(None, parse_move(&board, "e2e5"));
+        assert_eq!(None, parse_move(&board, "Nf6"));
+    }
+
+    #[test]
+    fn options() {
+        for config in [
+            UciOptionConfig::Check {
+                name: "Ponder".to_string(),
+                default: Some(true),
+            },
+            UciOptionConfig::Spin {
+                name: "Hash".to_string(),
+                default: Some(16),
+                min: Some(1),
+                max: Some(1024),
+            },
+            UciOptionConfig::Combo {
+                name: "Play Style".to_string(),
+                default: Some("Normal".to_string()),
+                var: vec!["Solid".to_string(), "Normal".to_string()],
+            },
+            UciOptionConfig::Button {
+                name: "Clear Hash".to_string(),
+            },
+            UciOptionConfig::String {
+                name: "Book File".to_string(),
+                default: Some("book.bin".to_string()),
+            },
+        ] {
+            assert_eq!(
+                Some(config.clone()),
+                parse_option_feature(&option_feature(&config))
+            );
+        }
+        assert_eq!(None, parse_option_feature("Hash -spin 16"));
+        assert_eq!(None, parse_option_feature("Hash"));
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chess::{Board, BoardStatus, ChessMove, Color};
+use vampirc_uci::{
+    UciFen, UciInfoAttribute, UciMessage, UciOptionConfig, UciSearchControl, UciTimeControl,
+};
+
+use crate::cecp::{option_feature, parse_move, CecpMessage, Outbox, Translate, MATE_SCORE};
+use crate::uci::client::{is_best_move, Handshake};
+use crate::uci::options::name_of;
+
+type Out = Outbox<CecpMessage, UciMessage>;
+
+/// Speaks CECP to a GUI (like XBoard) on behalf of a UCI engine. The game's
+/// kept here, and the engine's sent the whole of it each time it's to search.
+pub struct Adapter {
+    name: String,
+    options: Vec<UciOptionConfig>,
+    /// The position set up with `setboard`, if not the start.
+    fen: Option<String>,
+    moves: Vec<ChessMove>,
+    board: Board,
+    /// The side the engine plays, if any, as it doesn't in force mode.
+    engine_side: Option<Color>,
+    analyzing: bool,
+    searching: bool,
+    /// Searches stopped without wanting their `bestmove`, which is ignored.
+    stale: usize,
+    /// `level`'s moves per session and increment.
+    level: Option<(u32, Duration)>,
+    st: Option<Duration>,
+    sd: Option<u8>,
+    time: Option<Duration>,
+    otim: Option<Duration>,
+    post: bool,
+    /// Pings waiting on `readyok`s, to be answered in order.
+    pings: VecDeque<u32>,
+    quit: bool,
+}
+
+/// How a finished game ended, as CECP claims it.
+fn claim(board: &Board) -> Option<CecpMessage> {
+    let (result, comment) = match (board.status(), board.side_to_move()) {
+        (BoardStatus::Ongoing, _) => return None,
+        (BoardStatus::Stalemate, _) => ("1/2-1/2", "Stalemate"),
+        (BoardStatus::Checkmate, Color::White) => ("0-1", "Black mates"),
+        (BoardStatus::Checkmate, Color::Black) => ("1-0", "White mates"),
+    };
+    Some(CecpMessage::Claim {
+        result: result.to_string(),
+        comment: comment.to_string(),
+    })
+}
+
+impl Adapter {
+    /// For the engine which shook hands with `handshake`.
+    pub fn new(handshake: &Handshake) -> Adapter {
+        Adapter {
+            name: handshake
+                .name
+                .clone()
+                .unwrap_or_else(|| "UCI engine".to_string()),
+            options: handshake.options.uci_options(),
+            fen: None,
+            moves: vec![],
+            board: Board::default(),
+            engine_side: Some(Color::Black),
+            analyzing: false,
+            searching: false,
+            stale: 0,
+            level: None,
+            st: None,
+            sd: None,
+            time: None,
+            otim: None,
+            post: false,
+            pings: VecDeque::new(),
+            quit: false,
+        }
+    }
+
+    fn features(&self) -> CecpMessage {
+        let mut features: Vec<(String, String)> = [
+            ("myname", self.name.as_str()),
+            ("setboard", "1"),
+            ("usermove", "1"),
+            ("ping", "1"),
+            ("playother", "1"),
+            ("analyze", "1"),
+            ("colors", "0"),
+            ("sigint", "0"),
+            ("sigterm", "0"),
+            ("reuse", "1"),
+            ("variants", "normal"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .to_vec();
+        for config in &self.options {
+            features.push(("option".to_string(), option_feature(config)));
+        }
+        features.push(("done".to_string(), "1".to_string()));
+        CecpMessage::Feature(features)
+    }
+
+    /// Start over from the game's start, or the position set up.
+    fn replay(&mut self) {
+        self.board = match &self.fen {
+            Some(fen) => Board::from_str(fen).expect("FEN should have been checked"),
+            None => Board::default(),
+        };
+        for &m in &self.moves {
+            self.board = self.board.make_move_new(m);
+        }
+    }
+
+    /// Stop searching, if it is, ignoring what it finds.
+    fn abandon(&mut self, out: &mut Out) {
+        if self.searching {
+            self.searching = false;
+            self.stale += 1;
+            out.to_engine.push(UciMessage::Stop);
+        }
+    }
+
+    /// Search, if it's the engine's move, or it's analyzing.
+    fn think(&mut self, out: &mut Out) {
+        let side = self.board.side_to_move();
+        if !self.analyzing && self.engine_side != Some(side) {
+            return;
+        }
+        if let Some(claim) = claim(&self.board) {
+            if !self.analyzing {
+                out.to_gui.push(claim);
+            }
+            return;
+        }
+        out.to_engine.push(UciMessage::Position {
+            startpos: self.fen.is_none(),
+            fen: self.fen.clone().map(UciFen),
+            moves: self.moves.clone(),
+        });
+        let time_control = if self.analyzing {
+            Some(UciTimeControl::Infinite)
+        } else if let Some(st) = self.st {
+            Some(UciTimeControl::MoveTime(st))
+        } else if self.time.is_some() || self.otim.is_some() {
+            let (moves, inc) = self.level.unwrap_or_default();
+            let inc = Some(inc).filter(|i| !i.is_zero());
+            let (white_time, black_time) = match side {
+                Color::White => (self.time, self.otim),
+                Color::Black => (self.otim, self.time),
+            };
+            Some(UciTimeControl::TimeLeft {
+                white_time,
+                black_time,
+                white_increment: inc,
+                black_increment: inc,
+                moves_to_go: self.moves_to_go(moves),
+            })
+        } else {
+            None
+        };
+        let search_control = match self.sd {
+            Some(depth) if !self.analyzing => Some(UciSearchControl::depth(depth)),
+            _ => None,
+        };
+        out.to_engine.push(UciMessage::Go {
+            time_control,
+            search_control,
+        });
+        self.searching = true;
+    }
+
+    /// Moves until the clock's topped up, if it ever is.
+    fn moves_to_go(&self, per_session: u32) -> Option<u8> {
+        if per_session == 0 {
+            return None;
+        }
+        // made by the side to move, whichever moved first
+        let made = (self.moves.len() / 2) as u32;
+        let to_go = per_session - made % per_session;
+        Some(to_go.min(u8::MAX as u32) as u8)
+    }
+
+    fn user_move(&mut self, text: &str, out: &mut Out) {
+        let Some(m) = parse_move(&self.board, text) else {
+            out.to_gui.push(CecpMessage::IllegalMove {
+                reason: None,
+                mv: text.to_string(),
+            });
+            return;
+        };
+        self.abandon(out);
+        self.moves.push(m);
+        self.board = self.board.make_move_new(m);
+        self.think(out);
+    }
+
+    fn take_back(&mut self, n: usize, out: &mut Out) {
+        self.abandon(out);
+        self.moves.truncate(self.moves.len().saturating_sub(n));
+        self.replay();
+        if self.analyzing {
+            self.think(out);
+        }
+    }
+
+    /// A CECP option's value, as UCI has it.
+    fn option_value(&self, name: &str, value: Option<String>) -> Option<String> {
+        let config = self
+            .options
+            .iter()
+            .find(|c| name_of(c).eq_ignore_ascii_case(name));
+        match (config, value.as_deref()) {
+            (Some(UciOptionConfig::Check { .. }), Some(v)) => Some((v == "1").to_string()),
+            _ => value,
+        }
+    }
+
+    fn thinking(&self, attrs: &[UciInfoAttribute]) -> Option<CecpMessage> {
+        let (mut depth, mut score, mut time, mut nodes, mut pv) = (None, None, None, 0, None);
+        for attr in attrs {
+            match attr {
+                UciInfoAttribute::Depth(d) => depth = Some(*d),
+                UciInfoAttribute::Score { cp, mate, .. } => {
+                    score = match (cp, mate) {
+                        (_, Some(n)) => Some(i32::from(*n).signum() * MATE_SCORE + i32::from(*n)),
+                        (Some(cp), _) => Some(*cp),
+                        _ => score,
+                    }
+                }
+                UciInfoAttribute::Time(t) => time = Some(*t),
+                UciInfoAttribute::Nodes(n) => nodes = *n,
+                UciInfoAttribute::Pv(moves) => pv = Some(moves),
+                _ => {}
+            }
+        }
+        let pv: Vec<String> = pv?.iter().map(ChessMove::to_string).collect();
+        Some(CecpMessage::Thinking {
+            depth: depth?,
+            score: score?,
+            time: time.unwrap_or_default(),
+            nodes,
+            pv: pv.join(" "),
+        })
+    }
+}
+
+impl Translate for Adapter {
+    type Gui = CecpMessage;
+    type Engine = UciMessage;
+
+    fn from_gui(&mut self, msg: CecpMessage) -> Out {
+        let mut out = Out::default();
+        match msg {
+            CecpMessage::Protover(_) => out.to_gui.push(self.features()),
+            CecpMessage::New => {
+                self.abandon(&mut out);
+                self.fen = None;
+                self.moves.clear();
+                self.replay();
+                self.engine_side = Some(Color::Black);
+                self.analyzing = false;
+                self.st = None;
+                self.sd = None;
+                out.to_engine.push(UciMessage::UciNewGame);
+            }
+            CecpMessage::Variant(v) if v != "normal" => out.to_gui.push(CecpMessage::Error {
+                kind: "unsupported variant".to_string(),
+                command: format!("variant {v}"),
+            }),
+            CecpMessage::Quit => {
+                self.abandon(&mut out);
+                self.quit = true;
+                out.to_engine.push(UciMessage::Quit);
+            }
+            CecpMessage::Force => {
+                self.abandon(&mut out);
+                self.engine_side = None;
+            }
+            CecpMessage::Go => {
+                self.engine_side = Some(self.board.side_to_move());
+                self.think(&mut out);
+            }
+            CecpMessage::PlayOther => self.engine_side = Some(!self.board.side_to_move()),
+            CecpMessage::Level {
+                moves, increment, ..
+            } => {
+                self.level = Some((moves, increment));
+                self.st = None;
+            }
+            CecpMessage::St(d) => self.st = Some(d),
+            CecpMessage::Sd(d) => self.sd = Some(d),
+            CecpMessage::Time(d) => self.time = Some(d),
+            CecpMessage::Otim(d) => self.otim = Some(d),
+            CecpMessage::UserMove(text) | CecpMessage::BareMove(text) => {
+                self.user_move(&text, &mut out)
+            }
+            CecpMessage::MoveNow => {
+                if self.searching && !self.analyzing {
+                    out.to_engine.push(UciMessage::Stop);
+                }
+            }
+            CecpMessage::Ping(n) => {
+                self.pings.push_back(n);
+                out.to_engine.push(UciMessage::IsReady);
+            }
+            CecpMessage::Result { .. } => {
+                self.abandon(&mut out);
+                self.engine_side = None;
+            }
+            CecpMessage::SetBoard(fen) => match Board::from_str(&fen) {
+                Ok(_) => {
+                    self.abandon(&mut out);
+                    self.fen = Some(fen);
+                    self.moves.clear();
+                    self.replay();
+                    if self.analyzing {
+                        self.think(&mut out);
+                    }
+                }
+                Err(_) => out.to_gui.push(CecpMessage::Error {
+                    kind: "illegal position".to_string(),
+                    command: format!("setboard {fen}"),
+                }),
+            },
+            CecpMessage::Undo => self.take_back(1, &mut out),
+            CecpMessage::Remove => self.take_back(2, &mut out),
+            CecpMessage::Post => self.post = true,
+            CecpMessage::NoPost => self.post = false,
+            CecpMessage::Analyze => {
+                self.abandon(&mut out);
+                self.analyzing = true;
+                self.engine_side = None;
+                self.think(&mut out);
+            }
+            CecpMessage::Exit => {
+                self.abandon(&mut out);
+                self.analyzing = false;
+            }
+            CecpMessage::Option { name, value } => {
+                let value = self.option_value(&name, value);
+                out.to_engine.push(UciMessage::SetOption { name, value });
+            }
+            CecpMessage::Unknown(command) => out.to_gui.push(CecpMessage::Error {
+                kind: "unknown command".to_string(),
+                command,
+            }),
+            // nothing to do, or only ever sent by engines
+            _ => {}
+        }
+        out
+    }
+
+    fn from_engine(&mut self, msg: UciMessage) -> Out {
+        let mut out = Out::default();
+        match msg {
+            UciMessage::ReadyOk => {
+                if let Some(n) = self.pings.pop_front() {
+                    out.to_gui.push(CecpMessage::Pong(n));
+                }
+            }
+            UciMessage::Info(attrs) if self.post && self.stale == 0 => {
+                out.to_gui.extend(self.thinking(&attrs));
+            }
+            msg if is_best_move(&msg) => {
+                if self.stale > 0 {
+                    self.stale -= 1;
+                    return out;
+                }
+                self.searching = false;
+                if self.analyzing {
+                    return out;
+                }
+                match msg {
+                    UciMessage::BestMove { best_move, .. } if self.board.legal(best_move) => {
+                        self.moves.push(best_move);
+                        self.board = self.board.make_move_new(best_move);
+                        out.to_gui.push(CecpMessage::Move(best_move.to_string()));
+                        out.to_gui.extend(claim(&self.board));
+                    }
+                    _ => out.to_gui.push(CecpMessage::Resign),
+                }
+            }
+            _ => {}
+        }
+        out
+    }
+
+    fn done(&self) -> bool {
+        self.quit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::task::block_on;
+    use futures::channel::mpsc::unbounded;
+    use futures::future::join;
+    use futures::{SinkExt, StreamExt};
+
+    use crate::cecp::{bridge, parse_line};
+    use crate::rachel::engine::Rachel;
+    use crate::uci::client::Client;
+    use crate::uci::options::Options;
+
+    use super::*;
+
+    fn adapter() -> Adapter {
+        let handshake = Handshake {
+            name: Some("Fake".to_string()),
+            author: None,
+            options: Options::new().check("Ponder", false),
+        };
+        Adapter::new(&handshake)
+    }
+
+    fn gui(adapter: &mut Adapter, line: &str) -> Out {
+        adapter.from_gui(parse_line(line).unwrap())
+    }
+
+    fn mv(text: &str) -> ChessMove {
+        ChessMove::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn features() {
+        let mut adapter = adapter();
+        let out = gui(&mut adapter, "protover 2");
+        let CecpMessage::Feature(features) = &out.to_gui[0] else {
+            panic!("Expected features, not {:?}", out.to_gui);
+        };
+        assert!(features.contains(&("myname".to_string(), "Fake".to_string())));
+        assert!(features.contains(&("option".to_string(), "Ponder -check 0".to_string())));
+        assert_eq!(
+            Some(&("done".to_string(), "1".to_string())),
+            features.last()
+        );
+        let out = gui(&mut adapter, "option Ponder=1");
+        let ponder = UciMessage::SetOption {
+            name: "Ponder".to_string(),
+            value: Some("true".to_string()),
+        };
+        assert_eq!(vec![ponder], out.to_engine);
+    }
+
+    #[test]
+    fn playing() {
+        let mut adapter = adapter();
+        gui(&mut adapter, "new");
+        gui(&mut adapter, "level 40 5 0");
+        gui(&mut adapter, "time 30000");
+        gui(&mut adapter, "otim 29000");
+        let out = gui(&mut adapter, "usermove e2e4");
+        assert_eq!(
+            vec![
+                UciMessage::Position {
+                    startpos: true,
+                    fen: None,
+                    moves: vec![mv("e2e4")],
+                },
+                UciMessage::Go {
+                    time_control: Some(UciTimeControl::TimeLeft {
+                        white_time: Some(Duration::from_secs(290)),
+                        black_time: Some(Duration::from_secs(300)),
+                        white_increment: None,
+                        black_increment: None,
+                        moves_to_go: Some(40),
+                    }),
+                    search_control: None,
+                },
+            ],
+            out.to_engine
+        );
+        let out = adapter.from_engine(UciMessage::best_move(mv("e7e5")));
+        assert_eq!(vec![CecpMessage::Move("e7e5".to_string())], out.to_gui);
+        // its move again
+        assert_eq!(2, gui(&mut adapter, "usermove g1f3").to_engine.len());
+        let out = gui(&mut adapter, "force");
+        assert_eq!(vec![UciMessage::Stop], out.to_engine);
+        // that search's move is ignored
+        assert!(adapter
+            .from_engine(UciMessage::best_move(mv("b8c6")))
+            .to_gui
+            .is_empty());
+        let out = gui(&mut adapter, "usermove e5e4");
+        assert_eq!(
+            vec![CecpMessage::IllegalMove {
+                reason: None,
+                mv: "e5e4".to_string(),
+            }],
+            out.to_gui
+        );
+        let out = gui(&mut adapter, "ping 3");
+        assert_eq!(vec![UciMessage::IsReady], out.to_engine);
+        assert_eq!(
+            vec![CecpMessage::Pong(3)],
+            adapter.from_engine(UciMessage::ReadyOk).to_gui
+        );
+        assert!(gui(&mut adapter, "xyzzy").to_gui[0]
+            .to_string()
+            .starts_with("Error"));
+    }
+
+    #[test]
+    fn mates() {
+        let mut adapter = adapter();
+        gui(&mut adapter, "setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let out = gui(&mut adapter, "go");
+        assert_eq!(2, out.to_engine.len());
+        let out = adapter.from_engine(UciMessage::best_move(mv("a1a8")));
+        let claim = CecpMessage::Claim {
+            result: "1-0".to_string(),
+            comment: "White mates".to_string(),
+        };
+        assert_eq!(
+            vec![CecpMessage::Move("a1a8".to_string()), claim],
+            out.to_gui
+        );
+    }
+
+    #[test]
+    fn rachel() {
+        block_on(async {
+            let mut client = Client::builtin(Rachel::new());
+            let handshake = client.uci(Duration::from_secs(10)).await.unwrap();
+            let mut engine_sink = client.take_sink();
+            let engine_stream = client.take_stream();
+            let (mut gui_tx, gui_rx) = unbounded::<std::io::Result<CecpMessage>>();
+            let (out_tx, mut out_rx) = unbounded();
+            let mut gui_sink = out_tx.sink_map_err(std::io::Error::other);
+            for line in ["xboard", "protover 2", "new", "sd 2", "usermove e2e4"] {
+                gui_tx.send(Ok(parse_line(line).unwrap())).await.unwrap();
+            }
+            // quits once it's had a move
+            let gui = async move {
+                let mut got = vec![];
+                while let Some(msg) = out_rx.next().await {
+                    let moved = matches!(msg, CecpMessage::Move(_));
+                    got.push(msg);
+                    if moved {
+                        break;
+                    }
+                }
+                gui_tx.send(Ok(CecpMessage::Quit)).await.unwrap();
+                got
+            };
+            let mut adapter = Adapter::new(&handshake);
+            let bridged = bridge(
+                &mut adapter,
+                gui_rx,
+                &mut gui_sink,
+                engine_stream,
+                &mut engine_sink,
+            );
+            let (bridged, got) = join(bridged, gui).await;
+            bridged.unwrap();
+            drop(engine_sink);
+            client.close(Duration::from_secs(10)).await.unwrap();
+
+            assert!(matches!(got[0], CecpMessage::Feature(_)));
+            let Some(CecpMessage::Move(reply)) = got.last() else {
+                panic!("Expected a move, not {got:?}");
+            };
+            let board = Board::default().make_move_new(mv("e2e4"));
+            assert!(parse_move(&board, reply).is_some());
+        });
+    }
+}
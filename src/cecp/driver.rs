@@ -0,0 +1,794 @@
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_std::future::timeout;
+use async_std::io;
+use async_std::process::{Child, Command};
+use async_std::task::block_on;
+use chess::{Board, ChessMove, Color, MoveGen};
+use vampirc_uci::{
+    UciFen, UciInfoAttribute, UciMessage, UciOptionConfig, UciSearchControl, UciTimeControl,
+};
+
+use crate::cecp::{
+    bridge, from_reader, parse_move, parse_option_feature, to_writer, CecpMessage, Outbox,
+    Translate, MATE_SCORE,
+};
+use crate::uci::client::Client;
+use crate::uci::duplex::Pipe;
+use crate::uci::options::name_of;
+use crate::uci::parse_go_ponder;
+
+/// How long an engine has to exit once it's hung up on, before it's killed.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+/// The depth sent with `sd` to lift an earlier one, as CECP has no other way.
+const NO_DEPTH_LIMIT: u8 = 99;
+
+type Out = Outbox<UciMessage, CecpMessage>;
+
+/// Speaks UCI to a GUI (like birch) on behalf of a CECP engine. The engine's
+/// kept in force mode, so it only moves when told to `go`. Each `position` is
+/// sent as the moves since the engine's own position, if it's on the way
+/// there, or else set up afresh.
+#[derive(Default)]
+pub struct Driver {
+    /// Features the engine asked for.
+    usermove: bool,
+    setboard: bool,
+    name: Option<String>,
+    options: Vec<UciOptionConfig>,
+    /// The engine's position, as far as it's been told.
+    board: Board,
+    /// Its position before the last move, to `undo` a `go ponder`'s back to.
+    before_last_move: Option<Board>,
+    /// The moves per session and increment last sent with `level`.
+    level: Option<(u32, Duration)>,
+    /// Whether the last `go` limited the depth with `sd`, which stays in
+    /// effect until it's lifted.
+    depth_limited: bool,
+    /// Whether the last `go` fixed the time per move with `st`, which stays in
+    /// effect until `level` replaces it.
+    fixed_time: bool,
+    pings: u32,
+    /// Whether a `go ponder` is waiting on `ponderhit`, as CECP engines
+    /// ponder as they see fit, rather than on a given move.
+    ponder: bool,
+    /// The clock a `go ponder` came with, to send on `ponderhit`.
+    ponder_clock: Option<UciTimeControl>,
+    /// And its other limits.
+    ponder_search: Option<UciSearchControl>,
+    searching: bool,
+    analyzing: bool,
+    /// The first move of the latest line of analysis, to answer `stop` with.
+    analysis_move: Option<ChessMove>,
+    quit: bool,
+}
+
+fn info_string(text: String) -> UciMessage {
+    UciMessage::Info(vec![UciInfoAttribute::String(text)])
+}
+
+/// A thinking line's score, in moves to mate (see [MATE_SCORE]), as many as
+/// UCI's `score mate` has room for, or else centipawns.
+fn uci_score(score: i32) -> UciInfoAttribute {
+    let distance = score.unsigned_abs();
+    if distance >= MATE_SCORE as u32 {
+        let moves = (distance - MATE_SCORE as u32).min(i8::MAX as u32) as i8;
+        UciInfoAttribute::from_mate(score.signum() as i8 * moves)
+    } else {
+        UciInfoAttribute::from_centipawns(score)
+    }
+}
+
+/// The `bestmove` for when there isn't one, which vampirc can't represent.
+fn null_best_move() -> UciMessage {
+    UciMessage::Unknown("bestmove 0000".to_string(), None)
+}
+
+impl Driver {
+    pub fn new() -> Driver {
+        Driver::default()
+    }
+
+    fn user_move(&mut self, m: ChessMove) -> CecpMessage {
+        self.before_last_move = Some(self.board);
+        self.board = self.board.make_move_new(m);
+        if self.usermove {
+            CecpMessage::UserMove(m.to_string())
+        } else {
+            CecpMessage::BareMove(m.to_string())
+        }
+    }
+
+    fn position(
+        &mut self,
+        startpos: bool,
+        fen: Option<&UciFen>,
+        moves: &[ChessMove],
+    ) -> Result<Vec<CecpMessage>, String> {
+        let base = match fen {
+            Some(UciFen(fen)) if !startpos => {
+                Board::from_str(fen).map_err(|e| format!("bad FEN '{fen}': {e}"))?
+            }
+            _ => Board::default(),
+        };
+        let mut boards = vec![base];
+        for &m in moves {
+            let last = boards[boards.len() - 1];
+            if !last.legal(m) {
+                return Err(format!("illegal move {m} in {last}"));
+            }
+            boards.push(last.make_move_new(m));
+        }
+        let mut msgs = vec![];
+        let start = match boards.iter().rposition(|b| *b == self.board) {
+            Some(i) => i,
+            None => {
+                // e.g., birch's position after its opponent's move
+                let board = self.board;
+                match MoveGen::new_legal(&board).find(|m| board.make_move_new(*m) == base) {
+                    Some(m) => msgs.push(self.user_move(m)),
+                    None => {
+                        if base != Board::default() && !self.setboard {
+                            return Err("engine doesn't support setboard".to_string());
+                        }
+                        msgs.extend([CecpMessage::New, CecpMessage::Force]);
+                        // `new` lifts `sd`, and resets the clocks
+                        self.level = None;
+                        self.depth_limited = false;
+                        if base != Board::default() {
+                            msgs.push(CecpMessage::SetBoard(base.to_string()));
+                        }
+                        self.board = base;
+                        self.before_last_move = None;
+                    }
+                }
+                0
+            }
+        };
+        for &m in &moves[start..] {
+            msgs.push(self.user_move(m));
+        }
+        Ok(msgs)
+    }
+
+    fn go(
+        &mut self,
+        time_control: Option<UciTimeControl>,
+        search_control: Option<UciSearchControl>,
+        out: &mut Out,
+    ) {
+        if let Some(UciTimeControl::Ponder) = time_control {
+            // nothing's sent until `ponderhit`
+            self.ponder = true;
+            self.ponder_clock = None;
+            self.ponder_search = search_control;
+            return;
+        }
+        match search_control.and_then(|sc| sc.depth) {
+            Some(depth) => {
+                self.depth_limited = true;
+                out.to_engine.push(CecpMessage::Sd(depth));
+            }
+            None if self.depth_limited => {
+                self.depth_limited = false;
+                out.to_engine.push(CecpMessage::Sd(NO_DEPTH_LIMIT));
+            }
+            None => {}
+        }
+        match time_control {
+            Some(UciTimeControl::Infinite) => {
+                self.analyzing = true;
+                self.analysis_move = None;
+                out.to_engine.push(CecpMessage::Analyze);
+                return;
+            }
+            Some(UciTimeControl::MoveTime(d)) => {
+                self.fixed_time = true;
+                out.to_engine.push(CecpMessage::St(d));
+            }
+            Some(UciTimeControl::TimeLeft {
+                white_time,
+                black_time,
+                white_increment,
+                black_increment,
+                moves_to_go,
+            }) => {
+                let (own, opp, inc) = match self.board.side_to_move() {
+                    Color::White => (white_time, black_time, white_increment),
+                    Color::Black => (black_time, white_time, black_increment),
+                };
+                let level = (moves_to_go.map_or(0, u32::from), inc.unwrap_or_default());
+                if self.level != Some(level) || self.fixed_time {
+                    self.level = Some(level);
+                    self.fixed_time = false;
+                    out.to_engine.push(CecpMessage::Level {
+                        moves: level.0,
+                        base: own.unwrap_or_default(),
+                        increment: level.1,
+                    });
+                }
+                out.to_engine.extend(own.map(CecpMessage::Time));
+                out.to_engine.extend(opp.map(CecpMessage::Otim));
+            }
+            Some(UciTimeControl::Ponder) | None => {}
+        }
+        self.searching = true;
+        out.to_engine.push(CecpMessage::Go);
+    }
+
+    fn stop(&mut self, out: &mut Out) {
+        if self.analyzing {
+            self.analyzing = false;
+            out.to_engine.push(CecpMessage::Exit);
+            out.to_gui.push(
+                self.analysis_move
+                    .take()
+                    .map_or_else(null_best_move, UciMessage::best_move),
+            );
+        } else if self.ponder {
+            // it never started, but the move it was to ponder is taken back,
+            // as the next position most likely follows another
+            self.ponder = false;
+            self.ponder_clock = None;
+            self.ponder_search = None;
+            if let Some(board) = self.before_last_move.take() {
+                self.board = board;
+                out.to_engine.push(CecpMessage::Undo);
+            }
+            out.to_gui.push(null_best_move());
+        } else if self.searching {
+            // its move will follow
+            out.to_engine.push(CecpMessage::MoveNow);
+        }
+    }
+
+    /// A setoption's value, as CECP has it.
+    fn option_value(&self, name: &str, value: Option<String>) -> Option<String> {
+        let config = self
+            .options
+            .iter()
+            .find(|c| name_of(c).eq_ignore_ascii_case(name));
+        match (config, value.as_deref()) {
+            (Some(UciOptionConfig::Check { .. }), Some(v)) => Some(
+                if v.eq_ignore_ascii_case("true") {
+                    "1"
+                } else {
+                    "0"
+                }
+                .to_string(),
+            ),
+            _ => value,
+        }
+    }
+
+    /// A line of thinking output's moves, as far as they're legal.
+    fn pv(&self, text: &str) -> Vec<ChessMove> {
+        let mut board = self.board;
+        let mut pv = vec![];
+        // skipping move numbers
+        for token in text.split_whitespace().filter(|t| !t.ends_with('.')) {
+            match parse_move(&board, token) {
+                Some(m) => {
+                    pv.push(m);
+                    board = board.make_move_new(m);
+                }
+                None => break,
+            }
+        }
+        pv
+    }
+
+    fn features(&mut self, features: Vec<(String, String)>, out: &mut Out) {
+        let mut done = false;
+        for (key, value) in features {
+            let accept = match key.as_str() {
+                "myname" => {
+                    self.name = Some(value);
+                    true
+                }
+                "usermove" => {
+                    self.usermove = value == "1";
+                    true
+                }
+                "setboard" => {
+                    self.setboard = value == "1";
+                    true
+                }
+                // moves are sent in coordinate notation
+                "san" => value == "0",
+                "option" => match parse_option_feature(&value) {
+                    Some(config) => {
+                        self.options.push(config);
+                        true
+                    }
+                    None => false,
+                },
+                "done" => {
+                    done = value == "1";
+                    true
+                }
+                _ => true,
+            };
+            out.to_engine.push(if accept {
+                CecpMessage::Accepted(key)
+            } else {
+                CecpMessage::Rejected(key)
+            });
+        }
+        if done {
+            out.to_engine.extend([
+                CecpMessage::New,
+                CecpMessage::Force,
+                CecpMessage::Post,
+                CecpMessage::Easy,
+            ]);
+            let name = self.name.as_deref().unwrap_or("CECP engine");
+            out.to_gui.push(UciMessage::id_name(name));
+            out.to_gui
+                .extend(self.options.iter().cloned().map(UciMessage::Option));
+            out.to_gui.push(UciMessage::UciOk);
+        }
+    }
+}
+
+impl Translate for Driver {
+    type Gui = UciMessage;
+    type Engine = CecpMessage;
+
+    fn from_gui(&mut self, msg: UciMessage) -> Out {
+        let mut out = Out::default();
+        match msg {
+            UciMessage::Uci => {
+                out.to_engine = vec![CecpMessage::Xboard, CecpMessage::Protover(2)];
+            }
+            UciMessage::IsReady => {
+                self.pings += 1;
+                out.to_engine.push(CecpMessage::Ping(self.pings));
+            }
+            UciMessage::SetOption { name, value } => {
+                let value = self.option_value(&name, value);
+                out.to_engine.push(CecpMessage::Option { name, value });
+            }
+            UciMessage::UciNewGame => {
+                self.board = Board::default();
+                self.before_last_move = None;
+                self.level = None;
+                out.to_engine = vec![CecpMessage::New, CecpMessage::Force];
+            }
+            UciMessage::Position {
+                startpos,
+                fen,
+                moves,
+            } => match self.position(startpos, fen.as_ref(), &moves) {
+                Ok(msgs) => out.to_engine = msgs,
+                Err(e) => out.to_gui.push(info_string(e)),
+            },
+            UciMessage::Go {
+                time_control,
+                search_control,
+            } => self.go(time_control, search_control, &mut out),
+            UciMessage::Stop => self.stop(&mut out),
+            UciMessage::PonderHit => {
+                if self.ponder {
+                    self.ponder = false;
+                    let clock = self.ponder_clock.take();
+                    let search = self.ponder_search.take();
+                    self.go(clock, search, &mut out);
+                }
+            }
+            UciMessage::Quit => {
+                self.quit = true;
+                out.to_engine.push(CecpMessage::Quit);
+            }
+            UciMessage::Unknown(text, _) => match parse_go_ponder(&text) {
+                Some((time_control, search_control)) => {
+                    self.go(Some(UciTimeControl::Ponder), search_control, &mut out);
+                    self.ponder_clock = time_control;
+                }
+                // e.g., `bench`, which it may know
+                None => out.to_engine.push(CecpMessage::Unknown(text)),
+            },
+            _ => {}
+        }
+        out
+    }
+
+    fn from_engine(&mut self, msg: CecpMessage) -> Out {
+        let mut out = Out::default();
+        match msg {
+            CecpMessage::Feature(features) => self.features(features, &mut out),
+            CecpMessage::Pong(_) => out.to_gui.push(UciMessage::ReadyOk),
+            CecpMessage::Move(text) => {
+                self.searching = false;
+                // it's left force mode, so goes back
+                out.to_engine.push(CecpMessage::Force);
+                match parse_move(&self.board, &text) {
+                    Some(m) => {
+                        self.before_last_move = Some(self.board);
+                        self.board = self.board.make_move_new(m);
+                        out.to_gui.push(UciMessage::best_move(m));
+                    }
+                    None => {
+                        out.to_gui.push(info_string(format!("illegal move {text}")));
+                        out.to_gui.push(null_best_move());
+                    }
+                }
+            }
+            CecpMessage::Thinking {
+                depth,
+                score,
+                time,
+                nodes,
+                pv,
+            } => {
+                let mut attrs = vec![
+                    UciInfoAttribute::Depth(depth),
+                    uci_score(score),
+                    UciInfoAttribute::Time(time),
+                    UciInfoAttribute::Nodes(nodes),
+                ];
+                let pv = self.pv(&pv);
+                if self.analyzing {
+                    self.analysis_move = pv.first().copied().or(self.analysis_move);
+                }
+                if !pv.is_empty() {
+                    attrs.push(UciInfoAttribute::Pv(pv));
+                }
+                out.to_gui.push(UciMessage::Info(attrs));
+            }
+            CecpMessage::Resign if self.searching => {
+                self.searching = false;
+                out.to_gui.push(info_string(msg.to_string()));
+                out.to_gui.push(null_best_move());
+            }
+            msg => out.to_gui.push(info_string(msg.to_string())),
+        }
+        out
+    }
+
+    fn done(&self) -> bool {
+        self.quit
+    }
+}
+
+/// Run `cmd`, a CECP (XBoard) engine, and talk UCI to it through a [Driver]
+/// on a thread.
+pub fn spawn(cmd: &str) -> io::Result<Client> {
+    let child = Command::new(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    Ok(Client::on_thread(move |gui| block_on(drive(child, gui))))
+}
+
+/// Speak UCI through `gui` on behalf of `engine`, a CECP engine's process,
+/// until the GUI quits or either hangs up. Then the engine's given a moment
+/// to exit, before it's killed.
+pub async fn drive(mut engine: Child, gui: Pipe) {
+    let stdin = engine.stdin.take().expect("Engine's stdin should be piped");
+    let stdout = engine
+        .stdout
+        .take()
+        .expect("Engine's stdout should be piped");
+    let mut engine_sink = to_writer(stdin);
+    let engine_stream = from_reader(io::BufReader::new(stdout));
+    let Pipe { stream, mut sink } = gui;
+    let mut driver = Driver::new();
+    if let Err(e) = bridge(
+        &mut driver,
+        stream,
+        &mut sink,
+        engine_stream,
+        &mut engine_sink,
+    )
+    .await
+    {
+        eprintln!("[CECP] ! {e}");
+    }
+    // hanging up on both
+    drop(sink);
+    drop(engine_sink);
+    if timeout(QUIT_TIMEOUT, engine.status()).await.is_err() {
+        eprintln!("[CECP] ! killed still-running engine");
+        let _ = engine.kill();
+        let _ = engine.status().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mv(text: &str) -> ChessMove {
+        ChessMove::from_str(text).unwrap()
+    }
+
+    fn position(fen: Option<&str>, moves: &[&str]) -> UciMessage {
+        UciMessage::Position {
+            startpos: fen.is_none(),
+            fen: fen.map(|f| UciFen(f.to_string())),
+            moves: moves.iter().map(|m| mv(m)).collect(),
+        }
+    }
+
+    /// A driver with its handshake done.
+    fn driver() -> Driver {
+        let mut driver = Driver::new();
+        driver.from_gui(UciMessage::Uci);
+        let features = [
+            ("myname", "Fake"),
+            ("usermove", "1"),
+            ("setboard", "1"),
+            ("option", "Ponder -check 0"),
+            ("done", "1"),
+        ];
+        let features = features
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .to_vec();
+        driver.from_engine(CecpMessage::Feature(features));
+        driver
+    }
+
+    #[test]
+    fn handshake() {
+        let mut driver = Driver::new();
+        let out = driver.from_gui(UciMessage::Uci);
+        assert_eq!(
+            vec![CecpMessage::Xboard, CecpMessage::Protover(2)],
+            out.to_engine
+        );
+        let features = [
+            ("myname", "Fake"),
+            ("san", "1"),
+            ("option", "Hash -spin 16 1 64"),
+        ];
+        let features = features
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .to_vec();
+        let out = driver.from_engine(CecpMessage::Feature(features));
+        assert_eq!(
+            vec![
+                CecpMessage::Accepted("myname".to_string()),
+                CecpMessage::Rejected("san".to_string()),
+                CecpMessage::Accepted("option".to_string()),
+            ],
+            out.to_engine
+        );
+        assert!(out.to_gui.is_empty());
+        let done = vec![("done".to_string(), "1".to_string())];
+        let out = driver.from_engine(CecpMessage::Feature(done));
+        assert_eq!(CecpMessage::New, out.to_engine[1]);
+        assert_eq!(
+            vec![
+                UciMessage::id_name("Fake"),
+                UciMessage::Option(driver.options[0].clone())
+            ],
+            out.to_gui[..2]
+        );
+        assert_eq!(Some(&UciMessage::UciOk), out.to_gui.last());
+
+        let out = driver.from_gui(UciMessage::IsReady);
+        assert_eq!(vec![CecpMessage::Ping(1)], out.to_engine);
+        let out = driver.from_engine(CecpMessage::Pong(1));
+        assert_eq!(vec![UciMessage::ReadyOk], out.to_gui);
+    }
+
+    #[test]
+    fn positions() {
+        let mut driver = driver();
+        let out = driver.from_gui(position(None, &["e2e4"]));
+        assert_eq!(
+            vec![CecpMessage::UserMove("e2e4".to_string())],
+            out.to_engine
+        );
+        let go = UciMessage::Go {
+            time_control: Some(UciTimeControl::TimeLeft {
+                white_time: Some(Duration::from_secs(50)),
+                black_time: Some(Duration::from_secs(60)),
+                white_increment: None,
+                black_increment: None,
+                moves_to_go: None,
+            }),
+            search_control: None,
+        };
+        let out = driver.from_gui(go);
+        assert_eq!(
+            vec![
+                CecpMessage::Level {
+                    moves: 0,
+                    base: Duration::from_secs(60),
+                    increment: Duration::ZERO,
+                },
+                CecpMessage::Time(Duration::from_secs(60)),
+                CecpMessage::Otim(Duration::from_secs(50)),
+                CecpMessage::Go,
+            ],
+            out.to_engine
+        );
+        let out = driver.from_engine(CecpMessage::Move("e5".to_string()));
+        assert_eq!(vec![CecpMessage::Force], out.to_engine);
+        assert_eq!(vec![UciMessage::best_move(mv("e7e5"))], out.to_gui);
+
+        // on from the engine's own move
+        let out = driver.from_gui(position(None, &["e2e4", "e7e5", "g1f3"]));
+        assert_eq!(
+            vec![CecpMessage::UserMove("g1f3".to_string())],
+            out.to_engine
+        );
+        // just the position, as birch sends it
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let out = driver.from_gui(position(Some(fen), &[]));
+        assert_eq!(
+            vec![CecpMessage::UserMove("b8c6".to_string())],
+            out.to_engine
+        );
+        // somewhere else entirely
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let out = driver.from_gui(position(Some(fen), &["e2e4"]));
+        assert_eq!(CecpMessage::New, out.to_engine[0]);
+        assert!(matches!(&out.to_engine[2], CecpMessage::SetBoard(f) if f.starts_with("4k3/")));
+        assert_eq!(
+            Some(&CecpMessage::UserMove("e2e4".to_string())),
+            out.to_engine.last()
+        );
+
+        let out = driver.from_gui(position(None, &["e2e5"]));
+        assert!(out.to_engine.is_empty());
+        assert!(matches!(out.to_gui[..], [UciMessage::Info(_)]));
+    }
+
+    #[test]
+    fn limits_lifted() {
+        let mut driver = driver();
+        driver.from_gui(position(None, &["e2e4"]));
+        let go = UciMessage::Go {
+            time_control: Some(UciTimeControl::MoveTime(Duration::from_secs(1))),
+            search_control: Some(UciSearchControl::depth(2)),
+        };
+        let out = driver.from_gui(go);
+        assert_eq!(
+            vec![
+                CecpMessage::Sd(2),
+                CecpMessage::St(Duration::from_secs(1)),
+                CecpMessage::Go,
+            ],
+            out.to_engine
+        );
+        driver.from_engine(CecpMessage::Move("e5".to_string()));
+        driver.from_gui(position(None, &["e2e4", "e7e5", "g1f3"]));
+        let go = UciMessage::Go {
+            time_control: Some(UciTimeControl::TimeLeft {
+                white_time: Some(Duration::from_secs(50)),
+                black_time: Some(Duration::from_secs(60)),
+                white_increment: None,
+                black_increment: None,
+                moves_to_go: None,
+            }),
+            search_control: None,
+        };
+        let out = driver.from_gui(go.clone());
+        assert_eq!(
+            vec![
+                CecpMessage::Sd(NO_DEPTH_LIMIT),
+                CecpMessage::Level {
+                    moves: 0,
+                    base: Duration::from_secs(60),
+                    increment: Duration::ZERO,
+                },
+                CecpMessage::Time(Duration::from_secs(60)),
+                CecpMessage::Otim(Duration::from_secs(50)),
+                CecpMessage::Go,
+            ],
+            out.to_engine
+        );
+        // once lifted, they stay that way
+        driver.from_engine(CecpMessage::Move("Nc6".to_string()));
+        driver.from_gui(position(None, &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]));
+        let out = driver.from_gui(go);
+        assert_eq!(
+            vec![
+                CecpMessage::Time(Duration::from_secs(60)),
+                CecpMessage::Otim(Duration::from_secs(50)),
+                CecpMessage::Go,
+            ],
+            out.to_engine
+        );
+    }
+
+    #[test]
+    fn pondering_with_clock() {
+        let mut driver = driver();
+        driver.from_gui(position(None, &["e2e4", "e7e5"]));
+        let text = "go ponder wtime 50000 btime 60000";
+        let out = driver.from_gui(UciMessage::Unknown(text.to_string(), None));
+        // nothing until it's a search of its own
+        assert!(out.to_engine.is_empty());
+        let out = driver.from_gui(UciMessage::PonderHit);
+        assert_eq!(
+            vec![
+                CecpMessage::Level {
+                    moves: 0,
+                    base: Duration::from_secs(50),
+                    increment: Duration::ZERO,
+                },
+                CecpMessage::Time(Duration::from_secs(50)),
+                CecpMessage::Otim(Duration::from_secs(60)),
+                CecpMessage::Go,
+            ],
+            out.to_engine
+        );
+    }
+
+    #[test]
+    fn ponder_miss() {
+        let mut driver = driver();
+        driver.from_gui(position(None, &["e2e4", "e7e5"]));
+        // pondering on the expected reply
+        let out = driver.from_gui(position(None, &["e2e4", "e7e5", "g1f3"]));
+        let nf3 = CecpMessage::UserMove("g1f3".to_string());
+        assert_eq!(vec![nf3], out.to_engine);
+        driver.from_gui(UciMessage::go_ponder());
+        // the opponent played something else
+        let out = driver.from_gui(UciMessage::Stop);
+        assert_eq!(vec![CecpMessage::Undo], out.to_engine);
+        assert_eq!(vec![null_best_move()], out.to_gui);
+        let out = driver.from_gui(position(None, &["e2e4", "e7e5", "b1c3"]));
+        let nc3 = CecpMessage::UserMove("b1c3".to_string());
+        assert_eq!(vec![nc3], out.to_engine);
+        let out = driver.from_gui(UciMessage::go());
+        assert_eq!(vec![CecpMessage::Go], out.to_engine);
+    }
+
+    #[test]
+    fn analysis() {
+        let mut driver = driver();
+        let out = driver.from_gui(UciMessage::Go {
+            time_control: Some(UciTimeControl::Infinite),
+            search_control: None,
+        });
+        assert_eq!(vec![CecpMessage::Analyze], out.to_engine);
+        let out = driver.from_engine(CecpMessage::Thinking {
+            depth: 3,
+            score: MATE_SCORE + 2,
+            time: Duration::from_millis(120),
+            nodes: 4000,
+            pv: "1. Nf3 Nf6 2. xyzzy".to_string(),
+        });
+        let UciMessage::Info(attrs) = &out.to_gui[0] else {
+            panic!("Expected info, not {:?}", out.to_gui);
+        };
+        assert!(attrs.contains(&UciInfoAttribute::from_mate(2)));
+        assert!(attrs.contains(&UciInfoAttribute::Pv(vec![mv("g1f3"), mv("g8f6")])));
+        let out = driver.from_gui(UciMessage::Stop);
+        assert_eq!(vec![CecpMessage::Exit], out.to_engine);
+        assert_eq!(vec![UciMessage::best_move(mv("g1f3"))], out.to_gui);
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(UciInfoAttribute::from_centipawns(-35), uci_score(-35));
+        assert_eq!(UciInfoAttribute::from_mate(-3), uci_score(-MATE_SCORE - 3));
+        // further than UCI can say
+        assert_eq!(
+            UciInfoAttribute::from_mate(127),
+            uci_score(MATE_SCORE + 1000)
+        );
+        assert_eq!(UciInfoAttribute::from_mate(-127), uci_score(i32::MIN));
+    }
+
+    #[test]
+    fn options() {
+        let mut driver = driver();
+        let out = driver.from_gui(UciMessage::SetOption {
+            name: "ponder".to_string(),
+            value: Some("true".to_string()),
+        });
+        let ponder = CecpMessage::Option {
+            name: "ponder".to_string(),
+            value: Some("1".to_string()),
+        };
+        assert_eq!(vec![ponder], out.to_engine);
+    }
+}
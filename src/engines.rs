@@ -0,0 +1,51 @@
+use std::io;
+
+use async_std::task::block_on;
+
+use crate::cecp::driver;
+use crate::rachel::engine::Rachel;
+use crate::uci::client::Client;
+
+/// In place of a command, plays Rachel on threads of the caller's own, rather
+/// than as a separate process.
+pub const BUILTIN_RACHEL: &str = "builtin:rachel";
+
+/// Prefixes the command of an engine which speaks CECP (XBoard), not UCI.
+pub const CECP_PREFIX: &str = "cecp:";
+
+/// The `host:port` of an engine spec, if it's one to connect to over TCP,
+/// rather than a command to run.
+fn tcp_address(spec: &str) -> Option<&str> {
+    let (host, port) = spec.rsplit_once(':')?;
+    let is_tcp = !host.is_empty() && !host.contains('/') && port.parse::<u16>().is_ok();
+    is_tcp.then_some(spec)
+}
+
+/// Start the engine `spec`: a command, a `host:port`, [BUILTIN_RACHEL], or a
+/// CECP engine's command after [CECP_PREFIX].
+pub fn open(spec: &str) -> io::Result<Client> {
+    if spec == BUILTIN_RACHEL {
+        Ok(Client::builtin(Rachel::new()))
+    } else if let Some(cmd) = spec.strip_prefix(CECP_PREFIX) {
+        driver::spawn(cmd)
+    } else if let Some(addr) = tcp_address(spec) {
+        block_on(Client::connect(addr))
+    } else {
+        Client::spawn(spec)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn specs() {
+        assert_eq!(Some("localhost:4000"), tcp_address("localhost:4000"));
+        assert_eq!(Some("192.168.1.20:4000"), tcp_address("192.168.1.20:4000"));
+        assert_eq!(None, tcp_address("/usr/local/bin/stockfish"));
+        assert_eq!(None, tcp_address("C:/engines/stockfish.exe"));
+        assert_eq!(None, tcp_address("./engine:v2"));
+        assert_eq!(None, tcp_address(BUILTIN_RACHEL));
+    }
+}
@@ -1,2 +1,9 @@
 pub mod birch;
+pub mod cecp;
+pub mod engines;
+pub mod perft;
+pub mod pgn;
+pub mod polyglot;
+pub mod rachel;
+pub mod syzygy;
 pub mod uci;
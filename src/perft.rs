@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chess::{Board, ChessMove, MoveGen};
+
+/// The number of leaf nodes in the legal move tree `depth` plies deep from
+/// `board`. The last ply is counted, not made.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = MoveGen::new_legal(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .map(|m| perft(&board.make_move_new(m), depth - 1))
+        .sum()
+}
+
+/// [perft], broken down by root move, for finding which subtree disagrees with
+/// a reference move generator.
+pub fn divide(board: &Board, depth: u32) -> Vec<(ChessMove, u64)> {
+    divide_until(board, depth, &AtomicBool::new(false)).expect("Divide can't be stopped")
+}
+
+/// [divide], giving up with nothing if `stop` is raised. It's checked between
+/// root moves, so a deep count takes a while to notice.
+pub fn divide_until(board: &Board, depth: u32, stop: &AtomicBool) -> Option<Vec<(ChessMove, u64)>> {
+    let mut counts = vec![];
+    for m in MoveGen::new_legal(board) {
+        if stop.load(Ordering::SeqCst) {
+            return None;
+        }
+        counts.push((m, perft(&board.make_move_new(m), depth.saturating_sub(1))));
+    }
+    counts.sort_by_key(|(m, _)| m.to_string());
+    Some(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P3/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    /// Node counts from https://www.chessprogramming.org/Perft_Results, by
+    /// depth from one.
+    const SUITE: [(&str, &[u64]); 6] = [
+        (START, &[20, 400, 8_902, 197_281]),
+        (KIWIPETE, &[48, 2_039, 97_862]),
+        (POSITION_3, &[14, 191, 2_812, 43_238]),
+        (POSITION_4, &[6, 264, 9_467]),
+        (POSITION_5, &[44, 1_486, 62_379]),
+        (POSITION_6, &[46, 2_079, 89_890]),
+    ];
+
+    /// Deeper counts, which take too long for every test run.
+    const DEEP_SUITE: [(&str, u32, u64); 6] = [
+        (START, 5, 4_865_609),
+        (KIWIPETE, 4, 4_085_603),
+        (POSITION_3, 5, 674_624),
+        (POSITION_4, 4, 422_333),
+        (POSITION_5, 4, 2_103_487),
+        (POSITION_6, 4, 3_894_594),
+    ];
+
+    #[test]
+    fn suite() {
+        for (fen, counts) in SUITE {
+            let board = Board::from_str(fen).unwrap();
+            for (i, &expected) in counts.iter().enumerate() {
+                let depth = i as u32 + 1;
+                assert_eq!(expected, perft(&board, depth), "{fen} at depth {depth}");
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn deep_suite() {
+        for (fen, depth, expected) in DEEP_SUITE {
+            let board = Board::from_str(fen).unwrap();
+            assert_eq!(expected, perft(&board, depth), "{fen} at depth {depth}");
+        }
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let board = Board::from_str(KIWIPETE).unwrap();
+        let counts = divide(&board, 2);
+        assert_eq!(48, counts.len());
+        assert_eq!(2_039, counts.iter().map(|(_, n)| n).sum::<u64>());
+        let castle = ChessMove::from_str("e1g1").unwrap();
+        assert!(counts.iter().any(|&(m, _)| m == castle));
+        assert_eq!(1, perft(&board, 0));
+    }
+}
@@ -0,0 +1,241 @@
+use std::str::FromStr;
+
+use chess::{Board, ChessMove, Color};
+
+/// How a game ended, per its PGN result.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl Outcome {
+    /// Parse a PGN result token. The unfinished `*` isn't an outcome.
+    pub fn from_pgn(result: &str) -> Option<Outcome> {
+        match result {
+            "1-0" => Some(Outcome::WhiteWins),
+            "0-1" => Some(Outcome::BlackWins),
+            "1/2-1/2" => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+
+    /// Points scored by `color`, doubled so a draw is a whole number.
+    pub fn half_points(&self, color: Color) -> u32 {
+        match (self, color) {
+            (Outcome::Draw, _) => 1,
+            (Outcome::WhiteWins, Color::White) | (Outcome::BlackWins, Color::Black) => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// One game from a PGN file: its tags, and its main line. Comments, NAGs and
+/// variations are discarded.
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// The position before the first move; from the FEN tag, if there is one.
+    pub start: Board,
+    pub moves: Vec<ChessMove>,
+    pub outcome: Option<Outcome>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A game as it's being read.
+#[derive(Default)]
+struct Partial {
+    tags: Vec<(String, String)>,
+    board: Option<Board>,
+    start: Option<Board>,
+    moves: Vec<ChessMove>,
+    result: Option<String>,
+    error: Option<String>,
+}
+
+impl Partial {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.moves.is_empty() && self.error.is_none()
+    }
+
+    fn play(&mut self, token: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        if self.board.is_none() {
+            let start = match self.tags.iter().find(|(n, _)| n == "FEN") {
+                Some((_, fen)) => match Board::from_str(fen) {
+                    Ok(b) => b,
+                    Err(e) => {
+                        self.error = Some(format!("Bad FEN '{fen}': {e}"));
+                        return;
+                    }
+                },
+                None => Board::default(),
+            };
+            self.start = Some(start);
+            self.board = Some(start);
+        }
+        let board = self.board.as_mut().unwrap();
+        let san = token
+            .trim_end_matches(['+', '#', '!', '?'])
+            .replace('0', "O");
+        match ChessMove::from_san(board, &san) {
+            Ok(m) => {
+                *board = board.make_move_new(m);
+                self.moves.push(m);
+            }
+            Err(_) => {
+                self.error = Some(format!(
+                    "Illegal move '{token}' after {} plies",
+                    self.moves.len()
+                ))
+            }
+        }
+    }
+
+    fn finish(self) -> Result<PgnGame, String> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let result = self.result.or_else(|| {
+            self.tags
+                .iter()
+                .find(|(n, _)| n == "Result")
+                .map(|(_, v)| v.clone())
+        });
+        Ok(PgnGame {
+            tags: self.tags,
+            start: self.start.unwrap_or_default(),
+            moves: self.moves,
+            outcome: result.as_deref().and_then(Outcome::from_pgn),
+        })
+    }
+}
+
+/// Parse a tag pair's inside, e.g. `Event "Casual game"`.
+fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let (name, value) = tag.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Read every game in `text`. A game which can't be read (e.g. an illegal
+/// move) is an `Err`, but doesn't stop the games after it from being read.
+pub fn parse_games(text: &str) -> Vec<Result<PgnGame, String>> {
+    let mut games = vec![];
+    let mut game = Partial::default();
+    let mut variation_depth = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if variation_depth == 0 => {
+                // a tag after the movetext is the next game's
+                if !game.moves.is_empty() || game.result.is_some() {
+                    games.push(std::mem::take(&mut game).finish());
+                }
+                let tag: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                if let Some(pair) = parse_tag(&tag) {
+                    game.tags.push(pair);
+                }
+            }
+            '{' => chars.by_ref().take_while(|&c| c != '}').for_each(drop),
+            ';' => chars.by_ref().take_while(|&c| c != '\n').for_each(drop),
+            '(' => variation_depth += 1,
+            ')' => variation_depth = (variation_depth - 1).max(0),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();[".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                if variation_depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+                if RESULTS.contains(&token.as_str()) {
+                    game.result = Some(token);
+                    games.push(std::mem::take(&mut game).finish());
+                    continue;
+                }
+                // move numbers, possibly run into their move, as in "1.e4"
+                let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !san.is_empty() {
+                    game.play(san);
+                }
+            }
+        }
+    }
+    if !game.is_empty() {
+        games.push(game.finish());
+    }
+    games
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PGN: &str = r#"
+[Event "Casual"]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {the usual} Nc6 (2... d6 3. d4) 3. Bb5 a6 $1 4.Ba4 Nf6
+5. O-O Be7 1-0
+
+[Event "Short"]
+[Result "1/2-1/2"]
+
+1. d4 d5 ; a comment to end of line
+2. c4 1/2-1/2
+
+[Event "Broken"]
+
+1. e4 Ke7?? 2. Qh5 *
+
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+1. e4+ Kd7 *
+"#;
+
+    #[test]
+    fn games() {
+        let games = parse_games(PGN);
+        assert_eq!(4, games.len());
+        let g = games[0].as_ref().unwrap();
+        assert_eq!(Some("Alice"), g.tag("White"));
+        assert_eq!(10, g.moves.len());
+        assert_eq!(Some(Outcome::WhiteWins), g.outcome);
+        assert_eq!("e1g1", g.moves[8].to_string());
+        let g = games[1].as_ref().unwrap();
+        assert_eq!(3, g.moves.len());
+        assert_eq!(Some(Outcome::Draw), g.outcome);
+        assert!(games[2].is_err());
+        let g = games[3].as_ref().unwrap();
+        assert_eq!(2, g.moves.len());
+        assert_eq!(None, g.outcome);
+        assert_ne!(Board::default(), g.start);
+    }
+
+    #[test]
+    fn half_points() {
+        assert_eq!(2, Outcome::WhiteWins.half_points(Color::White));
+        assert_eq!(0, Outcome::WhiteWins.half_points(Color::Black));
+        assert_eq!(1, Outcome::Draw.half_points(Color::Black));
+    }
+}
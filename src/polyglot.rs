@@ -0,0 +1,320 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chess::{Board, ChessMove, Color, File, Piece, Rank, Square, ALL_PIECES};
+use rand::Rng;
+
+use crate::polyglot::random::RANDOM64;
+
+pub mod builder;
+pub mod random;
+
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+/// Bytes per entry in a book file.
+pub const ENTRY_SIZE: usize = 16;
+
+/// Polyglot's hash of `board`, which is what its book files are keyed on. It
+/// is not the same as [Board::get_hash].
+pub fn hash(board: &Board) -> u64 {
+    let mut key = 0;
+    for piece in ALL_PIECES {
+        for sq in *board.pieces(piece) {
+            let white = board.color_on(sq) == Some(Color::White);
+            let kind = 2 * piece.to_index() + white as usize;
+            key ^= RANDOM64[64 * kind + sq.to_index()];
+        }
+    }
+    for (i, (color, kingside)) in [
+        (Color::White, true),
+        (Color::White, false),
+        (Color::Black, true),
+        (Color::Black, false),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let rights = board.castle_rights(color);
+        if (kingside && rights.has_kingside()) || (!kingside && rights.has_queenside()) {
+            key ^= RANDOM64[CASTLING_OFFSET + i];
+        }
+    }
+    if let Some(file) = en_passant_file(board) {
+        key ^= RANDOM64[EN_PASSANT_OFFSET + file.to_index()];
+    }
+    if board.side_to_move() == Color::White {
+        key ^= RANDOM64[TURN_OFFSET];
+    }
+    key
+}
+
+/// The en passant file, but only if the side to move has a pawn beside the one
+/// which just moved two squares, as Polyglot only hashes it then.
+fn en_passant_file(board: &Board) -> Option<File> {
+    let file = board.en_passant()?.get_file();
+    let us = board.side_to_move();
+    // the capturing pawn is on its fifth rank
+    let rank = match us {
+        Color::White => Rank::Fifth,
+        Color::Black => Rank::Fourth,
+    };
+    let idx = file.to_index();
+    [idx.checked_sub(1), Some(idx + 1).filter(|&f| f < 8)]
+        .into_iter()
+        .flatten()
+        .map(|f| Square::make_square(rank, File::from_index(f)))
+        .any(|sq| board.piece_on(sq) == Some(Piece::Pawn) && board.color_on(sq) == Some(us))
+        .then_some(file)
+}
+
+/// One move for one position, as stored in a book file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    /// Polyglot's move encoding; see [decode_move] and [encode_move].
+    pub raw_move: u16,
+    /// Relative frequency the move should be chosen with.
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl Entry {
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Entry {
+        Entry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+const PROMOTIONS: [Option<Piece>; 5] = [
+    None,
+    Some(Piece::Knight),
+    Some(Piece::Bishop),
+    Some(Piece::Rook),
+    Some(Piece::Queen),
+];
+
+fn square(file: u16, rank: u16) -> Square {
+    Square::make_square(
+        Rank::from_index(rank as usize),
+        File::from_index(file as usize),
+    )
+}
+
+/// Decode a book move for `board`, translating Polyglot's castling (king takes
+/// own rook) into the king's two-square move. The move isn't checked for
+/// legality.
+pub fn decode_move(board: &Board, raw: u16) -> ChessMove {
+    let to = square(raw & 7, (raw >> 3) & 7);
+    let from = square((raw >> 6) & 7, (raw >> 9) & 7);
+    let promotion = PROMOTIONS
+        .get(((raw >> 12) & 7) as usize)
+        .copied()
+        .flatten();
+    let to = if board.piece_on(from) == Some(Piece::King)
+        && board.color_on(to) == board.color_on(from)
+        && from.get_file() == File::E
+    {
+        match to.get_file() {
+            File::H => Square::make_square(to.get_rank(), File::G),
+            File::A => Square::make_square(to.get_rank(), File::C),
+            _ => to,
+        }
+    } else {
+        to
+    };
+    ChessMove::new(from, to, promotion)
+}
+
+/// Encode `m` (made from `board`) the way Polyglot does, with castling as king
+/// takes own rook.
+pub fn encode_move(board: &Board, m: ChessMove) -> u16 {
+    let from = m.get_source();
+    let mut to = m.get_dest();
+    let is_king = board.piece_on(from) == Some(Piece::King);
+    if is_king && from.get_file() == File::E {
+        to = match to.get_file() {
+            File::G => Square::make_square(to.get_rank(), File::H),
+            File::C => Square::make_square(to.get_rank(), File::A),
+            _ => to,
+        };
+    }
+    let promotion = PROMOTIONS
+        .iter()
+        .position(|&p| p == m.get_promotion())
+        .unwrap_or(0) as u16;
+    (to.get_file().to_index() as u16)
+        | (to.get_rank().to_index() as u16) << 3
+        | (from.get_file().to_index() as u16) << 6
+        | (from.get_rank().to_index() as u16) << 9
+        | promotion << 12
+}
+
+/// A Polyglot opening book, read entirely into memory.
+pub struct Book {
+    /// Sorted by key, as in the file.
+    entries: Vec<Entry>,
+}
+
+impl Book {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Book> {
+        Book::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Book> {
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Book size {} isn't a multiple of {ENTRY_SIZE}", bytes.len()),
+            ));
+        }
+        let mut entries: Vec<Entry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|c| Entry::from_bytes(c.try_into().unwrap()))
+            .collect();
+        // should already be, but lookups depend on it
+        entries.sort_by_key(|e| e.key);
+        Ok(Book { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The legal book moves for `board`, with their weights.
+    pub fn moves(&self, board: &Board) -> Vec<(ChessMove, u16)> {
+        let key = hash(board);
+        let start = self.entries.partition_point(|e| e.key < key);
+        self.entries[start..]
+            .iter()
+            .take_while(|e| e.key == key)
+            .map(|e| (decode_move(board, e.raw_move), e.weight))
+            .filter(|&(m, _)| board.legal(m))
+            .collect()
+    }
+
+    /// Choose a book move for `board`, at random in proportion to the moves'
+    /// weights. Zero-weight moves are only chosen if they're all that's left.
+    pub fn choose<R: Rng>(&self, board: &Board, rng: &mut R) -> Option<ChessMove> {
+        let moves = self.moves(board);
+        let total: u32 = moves.iter().map(|&(_, w)| w as u32).sum();
+        if total == 0 {
+            return moves.first().map(|&(m, _)| m);
+        }
+        let mut pick = rng.gen_range(0..total);
+        for (m, w) in moves {
+            if pick < w as u32 {
+                return Some(m);
+            }
+            pick -= w as u32;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn play(moves: &str) -> Board {
+        moves.split_whitespace().fold(Board::default(), |b, m| {
+            b.make_move_new(ChessMove::from_str(m).unwrap())
+        })
+    }
+
+    /// From the Polyglot book format's specification.
+    #[test]
+    fn hash_keys() {
+        let cases = [
+            ("", 0x463b96181691fc9c),
+            ("e2e4", 0x823c9b50fd114196),
+            ("e2e4 d7d5", 0x0756b94461c50fb0),
+            ("e2e4 d7d5 e4e5", 0x662fafb965db29d4),
+            ("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560),
+        ];
+        for (moves, key) in cases {
+            assert_eq!(key, hash(&play(moves)), "after '{moves}'");
+        }
+    }
+
+    #[test]
+    fn castling_moves() {
+        let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for m in ["e1g1", "e1c1", "e1f1", "a1a8"] {
+            let m = ChessMove::from_str(m).unwrap();
+            assert_eq!(m, decode_move(&board, encode_move(&board, m)));
+        }
+        // e1h1, as Polyglot has it
+        let raw = 7 | 4 << 6;
+        assert_eq!(
+            raw,
+            encode_move(&board, ChessMove::from_str("e1g1").unwrap())
+        );
+    }
+
+    #[test]
+    fn promotion_moves() {
+        let board = Board::from_str("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let m = ChessMove::from_str("b7b8n").unwrap();
+        let raw = encode_move(&board, m);
+        assert_eq!(1, raw >> 12);
+        assert_eq!(m, decode_move(&board, raw));
+    }
+
+    #[test]
+    fn weighted_choice() {
+        let board = Board::default();
+        let key = hash(&board);
+        let entry = |m: &str, weight| Entry {
+            key,
+            raw_move: encode_move(&board, ChessMove::from_str(m).unwrap()),
+            weight,
+            learn: 0,
+        };
+        let bytes: Vec<u8> = [entry("e2e4", 3), entry("d2d4", 1), entry("a2a3", 0)]
+            .iter()
+            .flat_map(|e| e.to_bytes())
+            .collect();
+        let book = Book::from_bytes(&bytes).unwrap();
+        assert_eq!(3, book.moves(&board).len());
+        assert!(book.moves(&play("e2e4")).is_empty());
+        let mut rng = StdRng::seed_from_u64(0);
+        let e4 = ChessMove::from_str("e2e4").unwrap();
+        let e4s = (0..1000)
+            .filter(|_| book.choose(&board, &mut rng) == Some(e4))
+            .count();
+        assert!((650..850).contains(&e4s), "e4 chosen {e4s} times");
+        assert_ne!(
+            Some(ChessMove::from_str("a2a3").unwrap()),
+            book.choose(&board, &mut rng)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::pgn::PgnGame;
+use crate::polyglot::{encode_move, hash, Entry};
+
+/// Games played, and points scored, with one move from one position, from the
+/// mover's point of view.
+#[derive(Clone, Copy, Debug, Default)]
+struct Stats {
+    games: u32,
+    half_points: u32,
+}
+
+/// Accumulates move statistics from finished games, then turns the moves which
+/// were played often enough, and did well enough, into a Polyglot book.
+pub struct BookBuilder {
+    /// Only moves made in the first this-many plies are considered.
+    pub max_ply: usize,
+    /// Moves played fewer times than this are left out.
+    pub min_games: u32,
+    /// Moves scoring less than this fraction (0 to 1) for the side which made
+    /// them are left out.
+    pub min_score: f64,
+    stats: HashMap<(u64, u16), Stats>,
+    games: usize,
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        BookBuilder {
+            max_ply: 20,
+            min_games: 3,
+            min_score: 0.0,
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+}
+
+impl BookBuilder {
+    /// Add `game`'s moves to the statistics, returning whether it was used.
+    /// Unfinished games say nothing about how good their moves are, so they
+    /// aren't.
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        let Some(outcome) = game.outcome else {
+            return false;
+        };
+        let mut board = game.start;
+        for &m in game.moves.iter().take(self.max_ply) {
+            let key = (hash(&board), encode_move(&board, m));
+            let stats = self.stats.entry(key).or_default();
+            stats.games += 1;
+            stats.half_points += outcome.half_points(board.side_to_move());
+            board = board.make_move_new(m);
+        }
+        self.games += 1;
+        true
+    }
+
+    /// The number of games added.
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// The book's entries, in file order: by key, then best first. Weights are
+    /// points scored (two for a win, one for a draw), scaled down if needed to
+    /// fit Polyglot's sixteen bits.
+    pub fn build(&self) -> Vec<Entry> {
+        let mut entries: Vec<(u64, u16, u32)> = self
+            .stats
+            .iter()
+            .filter(|(_, s)| {
+                s.games >= self.min_games.max(1)
+                    && s.half_points as f64 / (2 * s.games) as f64 >= self.min_score
+            })
+            .map(|(&(key, raw_move), s)| (key, raw_move, s.half_points))
+            .collect();
+        let max = entries.iter().map(|e| e.2).max().unwrap_or(0);
+        let scale = |w: u32| {
+            if max <= u16::MAX as u32 {
+                w as u16
+            } else {
+                (w as u64 * u16::MAX as u64 / max as u64) as u16
+            }
+        };
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)).then(a.1.cmp(&b.1)));
+        entries
+            .into_iter()
+            .map(|(key, raw_move, points)| Entry {
+                key,
+                raw_move,
+                weight: scale(points),
+                learn: 0,
+            })
+            .collect()
+    }
+
+    /// Build the book, and write it to `out`, returning the number of entries.
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<usize> {
+        let entries = self.build();
+        for e in &entries {
+            out.write_all(&e.to_bytes())?;
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove};
+
+    use crate::pgn::parse_games;
+    use crate::polyglot::Book;
+
+    use super::*;
+
+    const PGN: &str = "
+1. e4 e5 2. Nf3 1-0
+1. e4 e5 2. Nf3 1/2-1/2
+1. e4 c5 0-1
+1. d4 d5 0-1
+1. c4 *
+";
+
+    fn builder(min_games: u32, min_score: f64) -> BookBuilder {
+        let mut builder = BookBuilder {
+            max_ply: 2,
+            min_games,
+            min_score,
+            ..BookBuilder::default()
+        };
+        for g in parse_games(PGN) {
+            builder.add_game(&g.unwrap());
+        }
+        builder
+    }
+
+    #[test]
+    fn statistics() {
+        let builder = builder(1, 0.0);
+        assert_eq!(4, builder.games());
+        let mut bytes = vec![];
+        assert_eq!(5, builder.write(&mut bytes).unwrap());
+        let book = Book::from_bytes(&bytes).unwrap();
+        let moves = book.moves(&Board::default());
+        // e4 scored a win, a draw and a loss; d4 only lost
+        assert_eq!(ChessMove::from_str("e2e4").unwrap(), moves[0].0);
+        assert_eq!(3, moves[0].1);
+        assert_eq!(0, moves[1].1);
+        // beyond max_ply
+        let after = Board::default()
+            .make_move_new(ChessMove::from_str("e2e4").unwrap())
+            .make_move_new(ChessMove::from_str("e7e5").unwrap());
+        assert!(book.moves(&after).is_empty());
+    }
+
+    #[test]
+    fn filters() {
+        // only e4, and e5 in reply to it, were played twice
+        assert_eq!(2, builder(2, 0.0).build().len());
+        // e4's 50% is too little, as is e5's; c5 and d5 won
+        let entries = builder(1, 0.6).build();
+        assert_eq!(2, entries.len());
+        assert!(entries.iter().all(|e| e.weight == 2));
+    }
+}
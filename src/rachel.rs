@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use chess::{Board, ChessMove, Error};
+use vampirc_uci::UciFen;
+
+pub mod bench;
+pub mod engine;
+pub mod eval;
+pub mod move_picker;
+pub mod own_book;
+pub mod search;
+pub mod search_thread;
+pub mod see;
+pub mod skill;
+pub mod time;
+pub mod tt;
+
+/// The position Rachel has been asked to search, along with the hashes of the
+/// positions which preceded it, for detecting repetitions.
+#[derive(Clone, Debug)]
+pub struct GameState {
+    pub board: Board,
+    pub history: Vec<u64>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState {
+            board: Board::default(),
+            history: vec![],
+        }
+    }
+}
+
+impl GameState {
+    /// Build from the parts of a UCI `position` message.
+    pub fn from_uci(
+        startpos: bool,
+        fen: Option<&UciFen>,
+        moves: &[ChessMove],
+    ) -> Result<GameState, Error> {
+        let board = match fen {
+            Some(fen) if !startpos => Board::from_str(&fen.0)?,
+            _ => Board::default(),
+        };
+        let mut state = GameState {
+            board,
+            history: vec![],
+        };
+        for &m in moves {
+            if !state.board.legal(m) {
+                return Err(Error::InvalidUciMove);
+            }
+            state.history.push(state.board.get_hash());
+            state.board = state.board.make_move_new(m);
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::ChessMove;
+
+    use super::*;
+
+    #[test]
+    fn from_uci_with_moves() {
+        let moves: Vec<ChessMove> = ["e2e4", "e7e5", "g1f3"]
+            .iter()
+            .map(|s| ChessMove::from_str(s).unwrap())
+            .collect();
+        let state = GameState::from_uci(true, None, &moves).unwrap();
+        assert_eq!(3, state.history.len());
+        assert_eq!(Board::default().get_hash(), state.history[0]);
+        assert_eq!(
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1",
+            state.board.to_string()
+        );
+    }
+
+    #[test]
+    fn from_uci_rejects_illegal_moves() {
+        let moves = vec![ChessMove::from_str("e2e5").unwrap()];
+        assert!(GameState::from_uci(true, None, &moves).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chess::Board;
+
+use crate::rachel::search::{Search, SearchParams, DEFAULT_HASH_MB};
+use crate::rachel::time::Limits;
+
+/// Depth each position is searched to, unless told otherwise.
+pub const BENCH_DEPTH: u8 = 5;
+
+/// A spread of openings, middlegames and endgames, with some tactics.
+const POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P3/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "2r3k1/pp3ppp/4p3/3pP3/3P4/P4N2/1P3PPP/2R3K1 b - - 0 24",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/8/2p5/8/B2K4/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+/// The totals from searching every bench position.
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl BenchResult {
+    pub fn nps(&self) -> u64 {
+        self.nodes * 1000 / self.time.as_millis().max(1) as u64
+    }
+
+    /// Lines to print, in Stockfish's order, so what reads its bench can read
+    /// this one. The node count, the signature, is second; [parse_nodes]
+    /// finds it by its label.
+    pub fn report(&self) -> [String; 3] {
+        [
+            format!("Total time (ms) : {}", self.time.as_millis()),
+            format!("{NODES_SEARCHED}{}", self.nodes),
+            format!("Nodes/second    : {}", self.nps()),
+        ]
+    }
+}
+
+const NODES_SEARCHED: &str = "Nodes searched  : ";
+
+/// Search each bench position to `depth` with a fresh search, so the node
+/// count depends only on `params` and the code. Any change in it means the
+/// search's behavior changed.
+pub fn bench(depth: u8, params: &SearchParams) -> BenchResult {
+    bench_until(depth, params, &Arc::new(AtomicBool::new(false))).expect("Bench can't be stopped")
+}
+
+/// [bench], giving up with nothing if `stop` is raised, as it is by `stop` when
+/// Rachel runs it for the GUI.
+pub fn bench_until(
+    depth: u8,
+    params: &SearchParams,
+    stop: &Arc<AtomicBool>,
+) -> Option<BenchResult> {
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in POSITIONS {
+        let board = Board::from_str(fen).expect("Bench positions should be valid");
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        search.params = params.clone();
+        search.set_stop_flag(stop.clone());
+        search.search(&board, &[], &Limits::depth(depth), |_| {});
+        if stop.load(Ordering::SeqCst) {
+            return None;
+        }
+        nodes += search.nodes();
+    }
+    Some(BenchResult {
+        nodes,
+        time: start.elapsed(),
+    })
+}
+
+/// The node count from a line of a bench report, if it's that line.
+pub fn parse_nodes(line: &str) -> Option<u64> {
+    line.trim()
+        .strip_prefix(NODES_SEARCHED.trim_end())?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let a = bench(3, &SearchParams::default());
+        let b = bench(3, &SearchParams::default());
+        assert_eq!(a.nodes, b.nodes);
+        assert_ne!(a.nodes, bench(3, &SearchParams::unpruned()).nodes);
+        assert_eq!(Some(a.nodes), parse_nodes(&a.report()[1]));
+        assert_eq!(None, parse_nodes(&a.report()[2]));
+    }
+}
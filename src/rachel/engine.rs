@@ -0,0 +1,324 @@
+use std::mem;
+use std::sync::Arc;
+
+use chess::ChessMove;
+use vampirc_uci::{UciFen, UciMessage, UciOptionConfig, UciSearchControl, UciTimeControl};
+
+use crate::perft::divide_until;
+use crate::rachel::bench::{bench_until, BENCH_DEPTH};
+use crate::rachel::own_book::OwnBook;
+use crate::rachel::search::{Search, SearchParams, DEFAULT_HASH_MB};
+use crate::rachel::search_thread::SearchThread;
+use crate::rachel::skill::Skill;
+use crate::rachel::time::Limits;
+use crate::rachel::GameState;
+use crate::syzygy::Tablebases;
+use crate::uci::engine::Engine;
+use crate::uci::options::Options;
+use crate::uci::UciSender;
+
+/// Thinking on the opponent's time.
+const OPT_PONDER: &str = "Ponder";
+/// Lines to search and report, for analysis.
+const OPT_MULTI_PV: &str = "MultiPV";
+const MAX_MULTI_PV: i64 = 256;
+/// Threads to search with.
+const OPT_THREADS: &str = "Threads";
+const MAX_THREADS: i64 = 64;
+/// Directories of Syzygy tables, separated as UCI has it.
+const OPT_SYZYGY_PATH: &str = "SyzygyPath";
+
+/// Rachel's side of UCI, for [crate::uci::engine::run_engine] to drive.
+pub struct Rachel {
+    search: SearchThread,
+    state: GameState,
+    book: OwnBook,
+    /// All of them, the search's and the book's included, each applying
+    /// itself to Rachel as it's set.
+    options: Options<Rachel>,
+}
+
+impl Default for Rachel {
+    fn default() -> Self {
+        Rachel::new()
+    }
+}
+
+impl Rachel {
+    pub fn new() -> Rachel {
+        Rachel {
+            search: SearchThread::new(Search::new(DEFAULT_HASH_MB)),
+            state: GameState::default(),
+            book: OwnBook::default(),
+            options: Rachel::declare(),
+        }
+    }
+
+    /// Rachel's options. Those that change the search stop it first; the
+    /// book's don't need to.
+    fn declare() -> Options<Rachel> {
+        const DECLARED: &str = "Option was just declared";
+        let options: Options<Rachel> =
+            SearchParams::declare(Options::new(), |r| &mut r.search.search_mut().params);
+        let options = OwnBook::declare(options, |r| &mut r.book);
+        Skill::declare(options, |r| &mut r.search.search_mut().skill)
+            // declared per the UCI spec by an engine which can ponder, though
+            // it's the GUI's `go ponder` which matters, so there's nothing to
+            // change, mid-search or not
+            .check(OPT_PONDER, false)
+            .spin(OPT_MULTI_PV, 1, 1, MAX_MULTI_PV)
+            .on_change(OPT_MULTI_PV, |r, value| {
+                r.search.search_mut().multi_pv = value.as_spin().expect(DECLARED) as usize;
+                Ok(())
+            })
+            .expect(DECLARED)
+            .spin(OPT_THREADS, 1, 1, MAX_THREADS)
+            .on_change(OPT_THREADS, |r, value| {
+                r.search.search_mut().threads = value.as_spin().expect(DECLARED) as usize;
+                Ok(())
+            })
+            .expect(DECLARED)
+            .string(OPT_SYZYGY_PATH, "")
+            .on_change(OPT_SYZYGY_PATH, |r, value| {
+                let engine = r.search.search_mut();
+                engine.tablebases = None;
+                match value.as_str().expect(DECLARED) {
+                    "" => {}
+                    path => {
+                        let tb = Tablebases::open(path)
+                            .map_err(|e| format!("can't load tables from '{path}': {e}"))?;
+                        eprintln!("[RACHEL] {}-piece tables loaded", tb.max_pieces());
+                        engine.tablebases = Some(Arc::new(tb));
+                    }
+                }
+                Ok(())
+            })
+            .expect(DECLARED)
+    }
+
+    /// Search the current position within `limits`, or to the default depth
+    /// if they've none.
+    fn start(&mut self, mut limits: Limits, sender: &UciSender) {
+        limits.bound();
+        self.search
+            .start(self.state.clone(), limits, sender.clone());
+    }
+}
+
+impl Engine for Rachel {
+    fn name(&self) -> String {
+        "Rachel".to_string()
+    }
+
+    fn author(&self) -> String {
+        "Barney Boisvert".to_string()
+    }
+
+    fn uci(&mut self) -> Vec<UciOptionConfig> {
+        self.options.uci_options()
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        // the options apply themselves to the rest of Rachel
+        let mut options = mem::take(&mut self.options);
+        let set = options.set(self, name, value);
+        self.options = options;
+        if !set? {
+            return Err(format!("unknown option: {name}"));
+        }
+        Ok(())
+    }
+
+    fn new_game(&mut self) {
+        self.search.search_mut().new_game();
+        self.state = GameState::default();
+    }
+
+    fn position(
+        &mut self,
+        startpos: bool,
+        fen: Option<&UciFen>,
+        moves: &[ChessMove],
+    ) -> Result<(), String> {
+        self.state = GameState::from_uci(startpos, fen, moves).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn go(
+        &mut self,
+        time_control: Option<UciTimeControl>,
+        search_control: Option<UciSearchControl>,
+        sender: &UciSender,
+    ) {
+        let limits = Limits::from_uci(
+            time_control.as_ref(),
+            search_control.as_ref(),
+            self.state.board.side_to_move(),
+        );
+        // pondering or analysing wants a search, not an instant move
+        if !limits.infinite && !limits.ponder && limits.search_moves.is_empty() {
+            if let Some(m) = self.book.probe(&self.state) {
+                let _ = sender.unbounded_send(UciMessage::best_move(m));
+                return;
+            }
+        }
+        self.start(limits, sender);
+    }
+
+    fn go_ponder(
+        &mut self,
+        time_control: Option<UciTimeControl>,
+        search_control: Option<UciSearchControl>,
+        sender: &UciSender,
+    ) {
+        let mut limits = Limits::from_uci(
+            time_control.as_ref(),
+            search_control.as_ref(),
+            self.state.board.side_to_move(),
+        );
+        limits.ponder = true;
+        self.start(limits, sender);
+    }
+
+    fn stop(&mut self) {
+        self.search.stop()
+    }
+
+    fn ponder_hit(&mut self) {
+        self.search.ponderhit()
+    }
+
+    /// `bench` and `go perft` run on the search thread, like a search, so the
+    /// message loop is free to answer `isready`, and `stop` or `quit` cuts them
+    /// short.
+    fn unknown(&mut self, text: &str, sender: &UciSender) -> bool {
+        if let Some(depth) = bench_depth(text) {
+            let params = self.search.search_mut().params.clone();
+            self.search.run(sender.clone(), move |stop| {
+                bench_until(depth, &params, stop).map_or(vec![], |b| b.report().into())
+            });
+        } else if let Some(depth) = perft_depth(text) {
+            let board = self.state.board;
+            self.search.run(sender.clone(), move |stop| {
+                let Some(counts) = divide_until(&board, depth, stop) else {
+                    return vec![];
+                };
+                let total: u64 = counts.iter().map(|(_, n)| n).sum();
+                counts
+                    .iter()
+                    .map(|(m, n)| format!("{m}: {n}"))
+                    .chain([String::new(), format!("Nodes searched: {total}")])
+                    .collect()
+            });
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+/// The depth of a `go perft N` command, which vampirc doesn't know, and so
+/// passes along unparsed.
+fn perft_depth(text: &str) -> Option<u32> {
+    match text.split_whitespace().collect::<Vec<_>>()[..] {
+        ["go", "perft", depth] => depth.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The depth of a `bench [N]` command, which isn't UCI, but is conventional.
+fn bench_depth(text: &str) -> Option<u8> {
+    match text.split_whitespace().collect::<Vec<_>>()[..] {
+        ["bench"] => Some(BENCH_DEPTH),
+        ["bench", depth] => depth.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::thread;
+
+    use async_std::task::block_on;
+    use chess::Board;
+    use futures::{SinkExt, StreamExt};
+
+    use crate::uci::duplex::duplex;
+    use crate::uci::engine::run_engine;
+    use crate::uci::UciStream;
+
+    use super::*;
+
+    /// Messages from `stream`, through the first which is `done`.
+    async fn until<F>(stream: &mut Box<UciStream>, done: F) -> Vec<UciMessage>
+    where
+        F: Fn(&UciMessage) -> bool,
+    {
+        let mut msgs = vec![];
+        while let Some(msg) = stream.next().await {
+            let msg = msg.unwrap();
+            let last = done(&msg);
+            msgs.push(msg);
+            if last {
+                break;
+            }
+        }
+        msgs
+    }
+
+    /// A short session, as a GUI would have it, with Rachel in-process.
+    #[test]
+    fn session() {
+        let (engine, mut gui) = duplex();
+        let rachel = thread::spawn(move || {
+            block_on(run_engine(&mut Rachel::new(), engine.stream, engine.sink))
+        });
+        block_on(async {
+            gui.sink.send(UciMessage::Uci).await.unwrap();
+            let msgs = until(&mut gui.stream, |m| *m == UciMessage::UciOk).await;
+            assert_eq!(UciMessage::id_name("Rachel"), msgs[0]);
+            assert!(msgs.iter().any(|m| matches!(
+                m,
+                UciMessage::Option(UciOptionConfig::Spin { name, .. }) if name == OPT_MULTI_PV
+            )));
+
+            let e4 = ChessMove::from_str("e2e4").unwrap();
+            for msg in [
+                UciMessage::SetOption {
+                    name: OPT_MULTI_PV.to_string(),
+                    value: Some("2".to_string()),
+                },
+                UciMessage::UciNewGame,
+                UciMessage::Position {
+                    startpos: true,
+                    fen: None,
+                    moves: vec![e4],
+                },
+                UciMessage::IsReady,
+            ] {
+                gui.sink.send(msg).await.unwrap();
+            }
+            let msgs = until(&mut gui.stream, |m| *m == UciMessage::ReadyOk).await;
+            assert_eq!(vec![UciMessage::ReadyOk], msgs);
+
+            let go = UciMessage::Go {
+                time_control: None,
+                search_control: Some(UciSearchControl::depth(3)),
+            };
+            gui.sink.send(go).await.unwrap();
+            let msgs = until(&mut gui.stream, |m| {
+                matches!(m, UciMessage::BestMove { .. })
+            })
+            .await;
+            let board = Board::default().make_move_new(e4);
+            match msgs.last() {
+                Some(UciMessage::BestMove { best_move, .. }) => assert!(board.legal(*best_move)),
+                m => panic!("expected bestmove, not {m:?}"),
+            }
+
+            gui.sink.send(UciMessage::Quit).await.unwrap();
+        });
+        rachel.join().unwrap();
+    }
+}
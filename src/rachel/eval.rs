@@ -0,0 +1,176 @@
+use chess::{Board, ChessMove, Color, Piece, ALL_PIECES, EMPTY};
+
+use crate::rachel::see::{attackers_to, least_valuable, see};
+
+/// Material values, indexed by [Piece::to_index].
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20_000];
+
+/// Piece-square tables from Tomasz Michniewski's "Simplified Evaluation
+/// Function", laid out as a board diagram from White's perspective (so a8 is
+/// the first entry, and h1 the last).
+#[rustfmt::skip]
+const PSTS: [[i32; 64]; 6] = [
+    // pawn
+    [
+         0,  0,  0,  0,  0,  0,  0,  0,
+        50, 50, 50, 50, 50, 50, 50, 50,
+        10, 10, 20, 30, 30, 20, 10, 10,
+         5,  5, 10, 25, 25, 10,  5,  5,
+         0,  0,  0, 20, 20,  0,  0,  0,
+         5, -5,-10,  0,  0,-10, -5,  5,
+         5, 10, 10,-20,-20, 10, 10,  5,
+         0,  0,  0,  0,  0,  0,  0,  0,
+    ],
+    // knight
+    [
+        -50,-40,-30,-30,-30,-30,-40,-50,
+        -40,-20,  0,  0,  0,  0,-20,-40,
+        -30,  0, 10, 15, 15, 10,  0,-30,
+        -30,  5, 15, 20, 20, 15,  5,-30,
+        -30,  0, 15, 20, 20, 15,  0,-30,
+        -30,  5, 10, 15, 15, 10,  5,-30,
+        -40,-20,  0,  5,  5,  0,-20,-40,
+        -50,-40,-30,-30,-30,-30,-40,-50,
+    ],
+    // bishop
+    [
+        -20,-10,-10,-10,-10,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5, 10, 10,  5,  0,-10,
+        -10,  5,  5, 10, 10,  5,  5,-10,
+        -10,  0, 10, 10, 10, 10,  0,-10,
+        -10, 10, 10, 10, 10, 10, 10,-10,
+        -10,  5,  0,  0,  0,  0,  5,-10,
+        -20,-10,-10,-10,-10,-10,-10,-20,
+    ],
+    // rook
+    [
+         0,  0,  0,  0,  0,  0,  0,  0,
+         5, 10, 10, 10, 10, 10, 10,  5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+         0,  0,  0,  5,  5,  0,  0,  0,
+    ],
+    // queen
+    [
+        -20,-10,-10, -5, -5,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5,  5,  5,  5,  0,-10,
+         -5,  0,  5,  5,  5,  5,  0, -5,
+          0,  0,  5,  5,  5,  5,  0, -5,
+        -10,  5,  5,  5,  5,  5,  0,-10,
+        -10,  0,  5,  0,  0,  0,  0,-10,
+        -20,-10,-10, -5, -5,-10,-10,-20,
+    ],
+    // king (middle game)
+    [
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -20,-30,-30,-40,-40,-30,-30,-20,
+        -10,-20,-20,-20,-20,-20,-20,-10,
+         20, 20,  0,  0,  0,  0, 20, 20,
+         20, 30, 10,  0,  0, 10, 30, 20,
+    ],
+];
+
+/// Penalty for each hanging piece the side to move has beyond the first. It
+/// can move one to safety, but the rest will be lost.
+const HANGING_PENALTY: i32 = 75;
+
+/// Value of `piece`, for material counting and capture ordering.
+pub fn piece_value(piece: Piece) -> i32 {
+    PIECE_VALUES[piece.to_index()]
+}
+
+/// Static evaluation of `board`, in centipawns, from the perspective of the
+/// side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    let white = evaluate_side(board, Color::White);
+    let black = evaluate_side(board, Color::Black);
+    let score = match board.side_to_move() {
+        Color::White => white - black,
+        Color::Black => black - white,
+    };
+    match hanging_pieces(board) {
+        0 | 1 => score,
+        n => score - (n - 1) * HANGING_PENALTY,
+    }
+}
+
+/// Count the side to move's pieces (other than pawns and its king) which the
+/// opponent could win material by capturing, were it their turn.
+pub fn hanging_pieces(board: &Board) -> i32 {
+    // no null move while in check, but then the check is the bigger problem
+    let Some(flipped) = board.null_move() else {
+        return 0;
+    };
+    let us = board.side_to_move();
+    let pawns_and_kings = board.pieces(Piece::Pawn) | board.pieces(Piece::King);
+    let candidates = board.color_combined(us) & !pawns_and_kings;
+    let mut count = 0;
+    for sq in candidates {
+        let attackers = attackers_to(board, sq, *board.combined()) & board.color_combined(!us);
+        if attackers == EMPTY {
+            continue;
+        }
+        if let Some((from, _)) = least_valuable(board, attackers) {
+            if see(&flipped, ChessMove::new(from, sq, None)) > 0 {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn evaluate_side(board: &Board, color: Color) -> i32 {
+    let mine = board.color_combined(color);
+    let mut score = 0;
+    for piece in ALL_PIECES {
+        let pst = &PSTS[piece.to_index()];
+        for sq in board.pieces(piece) & mine {
+            let rank = sq.get_rank().to_index();
+            let file = sq.get_file().to_index();
+            let idx = match color {
+                Color::White => (7 - rank) * 8 + file,
+                Color::Black => rank * 8 + file,
+            };
+            score += piece_value(piece) + pst[idx];
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::Board;
+
+    use super::*;
+
+    #[test]
+    fn initial_position_is_balanced() {
+        assert_eq!(0, evaluate(&Board::default()));
+    }
+
+    #[test]
+    fn mirrored_positions_agree() {
+        let white = Board::from_str("4k3/8/8/8/8/8/3QP3/4K3 w - - 0 1").unwrap();
+        let black = Board::from_str("4k3/3qp3/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(evaluate(&white) > 0);
+        assert_eq!(evaluate(&white), evaluate(&black));
+    }
+
+    #[test]
+    fn hanging() {
+        // a pawn forks the knights
+        let board = Board::from_str("4k3/8/3p4/2N1N3/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(0, hanging_pieces(&Board::default()));
+        assert_eq!(2, hanging_pieces(&board));
+    }
+}
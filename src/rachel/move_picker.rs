@@ -0,0 +1,234 @@
+use chess::{Board, ChessMove, MoveGen, Piece};
+
+use crate::rachel::eval::piece_value;
+use crate::rachel::search::SearchParams;
+use crate::rachel::see::see_ge;
+
+const HASH_MOVE_SCORE: i32 = i32::MAX;
+const NOISY_SCORE: i32 = 1_000_000;
+const KILLER_SCORE: i32 = 900_000;
+/// Captures which lose material go after every quiet move.
+const BAD_CAPTURE_SCORE: i32 = -1_000_000;
+/// History scores are kept below this, so quiet moves never outrank killers.
+const HISTORY_MAX: i32 = 100_000;
+
+/// The piece captured by `m`, if any, including en passant captures.
+pub fn captured_piece(board: &Board, m: ChessMove) -> Option<Piece> {
+    board.piece_on(m.get_dest()).or_else(|| {
+        let is_pawn = board.piece_on(m.get_source()) == Some(Piece::Pawn);
+        if is_pawn && m.get_source().get_file() != m.get_dest().get_file() {
+            Some(Piece::Pawn)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `m` neither captures nor promotes, which is what killers and the
+/// history table are concerned with.
+pub fn is_quiet(board: &Board, m: ChessMove) -> bool {
+    m.get_promotion().is_none() && captured_piece(board, m).is_none()
+}
+
+/// Most Valuable Victim - Least Valuable Attacker: prefer taking the biggest
+/// piece, and among equal victims, with the smallest piece.
+pub fn mvv_lva(board: &Board, m: ChessMove) -> i32 {
+    let victim = captured_piece(board, m).map_or(0, piece_value);
+    let attacker = board
+        .piece_on(m.get_source())
+        .map_or(0, |p| p.to_index() as i32);
+    let promotion = m.get_promotion().map_or(0, piece_value);
+    victim * 8 - attacker + promotion
+}
+
+/// Quiet move scores, indexed by source and destination square, rewarded each
+/// time the move causes a beta cutoff.
+pub struct History {
+    table: Box<[[i32; 64]; 64]>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            table: Box::new([[0; 64]; 64]),
+        }
+    }
+
+    pub fn get(&self, m: ChessMove) -> i32 {
+        self.table[m.get_source().to_index()][m.get_dest().to_index()]
+    }
+
+    pub fn reward(&mut self, m: ChessMove, depth: i32) {
+        let entry = &mut self.table[m.get_source().to_index()][m.get_dest().to_index()];
+        *entry += depth * depth;
+        if *entry >= HISTORY_MAX {
+            // age everything, so old cutoffs don't dominate forever
+            self.table
+                .iter_mut()
+                .flat_map(|row| row.iter_mut())
+                .for_each(|h| *h /= 2);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.table
+            .iter_mut()
+            .for_each(|row| row.iter_mut().for_each(|h| *h = 0));
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Yields legal moves best-first: the hash move, then captures and promotions
+/// by MVV-LVA, then killer moves for the ply, then quiet moves by history, and
+/// finally captures which [see_ge] says lose material.
+///
+/// Every move is generated and scored up front, but only sorted lazily, as a
+/// cutoff on an early move makes sorting the rest a waste.
+pub struct MovePicker {
+    moves: Vec<(ChessMove, i32)>,
+    next: usize,
+}
+
+impl MovePicker {
+    pub fn new(
+        board: &Board,
+        hash_move: Option<ChessMove>,
+        killers: &[Option<ChessMove>; 2],
+        history: &History,
+        params: &SearchParams,
+    ) -> MovePicker {
+        let moves = MoveGen::new_legal(board)
+            .map(|m| {
+                let score = if params.hash_move && Some(m) == hash_move {
+                    HASH_MOVE_SCORE
+                } else if !is_quiet(board, m) {
+                    let base = if params.see && !see_ge(board, m, 0) {
+                        BAD_CAPTURE_SCORE
+                    } else {
+                        NOISY_SCORE
+                    };
+                    if params.mvv_lva {
+                        base + mvv_lva(board, m)
+                    } else if params.see {
+                        base
+                    } else {
+                        0
+                    }
+                } else if params.killers && Some(m) == killers[0] {
+                    KILLER_SCORE + 1
+                } else if params.killers && Some(m) == killers[1] {
+                    KILLER_SCORE
+                } else if params.history {
+                    history.get(m)
+                } else {
+                    0
+                };
+                (m, score)
+            })
+            .collect();
+        MovePicker { moves, next: 0 }
+    }
+
+    /// Captures and promotions only, for quiescence search. Those which lose
+    /// material are skipped entirely.
+    pub fn noisy(board: &Board, params: &SearchParams) -> MovePicker {
+        let moves = MoveGen::new_legal(board)
+            .filter(|&m| !is_quiet(board, m))
+            .filter(|&m| !params.see || see_ge(board, m, 0))
+            .map(|m| {
+                let score = if params.mvv_lva { mvv_lva(board, m) } else { 0 };
+                (m, score)
+            })
+            .collect();
+        MovePicker { moves, next: 0 }
+    }
+}
+
+impl Iterator for MovePicker {
+    type Item = ChessMove;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.moves.len() {
+            return None;
+        }
+        let mut best = self.next;
+        for i in (self.next + 1)..self.moves.len() {
+            // strict, so equal scores keep generation order
+            if self.moves[i].1 > self.moves[best].1 {
+                best = i;
+            }
+        }
+        self.moves.swap(self.next, best);
+        self.next += 1;
+        Some(self.moves[self.next - 1].0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove};
+
+    use super::*;
+
+    #[test]
+    fn hash_move_first_then_captures() {
+        // white can take a queen with a pawn, or a rook with a pawn or queen
+        let board = Board::from_str("4k3/8/8/3q1r2/4P3/8/8/4KQ2 w - - 0 1").unwrap();
+        let hash_move = ChessMove::from_str("e1e2").ok();
+        let mut picker = MovePicker::new(
+            &board,
+            hash_move,
+            &[None, None],
+            &History::new(),
+            &SearchParams::default(),
+        );
+        assert_eq!(hash_move, picker.next());
+        assert_eq!(ChessMove::from_str("e4d5").ok(), picker.next());
+        assert_eq!(ChessMove::from_str("e4f5").ok(), picker.next());
+        assert_eq!(ChessMove::from_str("f1f5").ok(), picker.next());
+    }
+
+    #[test]
+    fn killers_before_history() {
+        let board = Board::default();
+        let killer = ChessMove::from_str("b1c3").ok();
+        let mut history = History::new();
+        history.reward(ChessMove::from_str("e2e4").unwrap(), 10);
+        let mut picker = MovePicker::new(
+            &board,
+            None,
+            &[killer, None],
+            &history,
+            &SearchParams::default(),
+        );
+        assert_eq!(killer, picker.next());
+        assert_eq!(ChessMove::from_str("e2e4").ok(), picker.next());
+        assert_eq!(18, picker.count());
+    }
+
+    #[test]
+    fn losing_captures_last() {
+        // the queen can take a defended pawn, or either piece a free one
+        let board = Board::from_str("4k3/2p5/3p4/5p2/8/3Q2N1/8/4K3 w - - 0 1").unwrap();
+        let params = SearchParams::default();
+        let picker = MovePicker::new(&board, None, &[None, None], &History::new(), &params);
+        let moves: Vec<ChessMove> = picker.collect();
+        assert_eq!(ChessMove::from_str("g3f5").ok(), moves.first().copied());
+        assert_eq!(ChessMove::from_str("d3d6").ok(), moves.last().copied());
+        let noisy: Vec<ChessMove> = MovePicker::noisy(&board, &params).collect();
+        assert_eq!(
+            vec![
+                ChessMove::from_str("g3f5").unwrap(),
+                ChessMove::from_str("d3f5").unwrap()
+            ],
+            noisy
+        );
+    }
+}
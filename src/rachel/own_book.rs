@@ -0,0 +1,106 @@
+use chess::ChessMove;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::polyglot::Book;
+use crate::rachel::GameState;
+use crate::uci::options::Options;
+
+const OPT_OWN_BOOK: &str = "OwnBook";
+const OPT_BOOK_FILE: &str = "BookFile";
+const OPT_BOOK_DEPTH: &str = "BookDepth";
+/// Plies into the game the book is consulted for, by default.
+const DEFAULT_BOOK_DEPTH: i64 = 20;
+const MAX_BOOK_DEPTH: i64 = 100;
+
+/// Rachel's opening book: a Polyglot file, consulted before searching for the
+/// first few moves of a game, if the GUI turns it on.
+pub struct OwnBook {
+    enabled: bool,
+    depth: usize,
+    book: Option<Book>,
+    rng: StdRng,
+}
+
+impl Default for OwnBook {
+    fn default() -> Self {
+        OwnBook {
+            enabled: false,
+            depth: DEFAULT_BOOK_DEPTH as usize,
+            book: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl OwnBook {
+    /// Declare the book's options in `options`, at their defaults, each
+    /// setting the book `book` finds in `T`. Setting the file loads it
+    /// straight away, so a bad one is reported then (leaving no book), rather
+    /// than silently ignored at the first `go`.
+    pub fn declare<T: 'static>(
+        options: Options<T>,
+        book: fn(&mut T) -> &mut OwnBook,
+    ) -> Options<T> {
+        const DECLARED: &str = "Book option was just declared";
+        options
+            .check(OPT_OWN_BOOK, false)
+            .on_change(OPT_OWN_BOOK, move |t, value| {
+                book(t).enabled = value.as_check().expect(DECLARED);
+                Ok(())
+            })
+            .expect(DECLARED)
+            .string(OPT_BOOK_FILE, "")
+            .on_change(OPT_BOOK_FILE, move |t, value| {
+                let book = book(t);
+                book.book = None;
+                match value.as_str().expect(DECLARED) {
+                    "" => {}
+                    path => {
+                        let opened = Book::open(path)
+                            .map_err(|e| format!("Can't read book '{path}': {e}"))?;
+                        book.book = Some(opened);
+                    }
+                }
+                Ok(())
+            })
+            .expect(DECLARED)
+            .spin(OPT_BOOK_DEPTH, DEFAULT_BOOK_DEPTH, 0, MAX_BOOK_DEPTH)
+            .on_change(OPT_BOOK_DEPTH, move |t, value| {
+                book(t).depth = value.as_spin().expect(DECLARED) as usize;
+                Ok(())
+            })
+            .expect(DECLARED)
+    }
+
+    /// A book move for `state`, if the book is on, loaded, still within its
+    /// depth, and has one.
+    pub fn probe(&mut self, state: &GameState) -> Option<ChessMove> {
+        if !self.enabled || state.history.len() >= self.depth {
+            return None;
+        }
+        self.book.as_ref()?.choose(&state.board, &mut self.rng)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn options() {
+        let mut book = OwnBook::default();
+        let mut options = OwnBook::declare(Options::new(), |b| b);
+        let mut set = |name: &str, value: &str| options.set(&mut book, name, Some(value));
+        assert_eq!(Ok(true), set("ownbook", "true"));
+        assert_eq!(Ok(true), set("BookDepth", "8"));
+        assert!(set("BookDepth", "-1").is_err());
+        assert!(set("BookFile", "/no/such/book.bin").is_err());
+        assert_eq!(Ok(true), set("BookFile", "<empty>"));
+        assert_eq!(Ok(false), set("Hash", "1"));
+        assert!(book.enabled);
+        assert_eq!(8, book.depth);
+        // on, but nothing loaded
+        assert_eq!(None, book.probe(&GameState::default()));
+    }
+}
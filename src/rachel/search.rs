@@ -0,0 +1,1216 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chess::{Board, ChessMove, MoveGen, Piece, EMPTY};
+use vampirc_uci::{UciInfoAttribute, UciMessage};
+
+use crate::rachel::eval::evaluate;
+use crate::rachel::move_picker::{is_quiet, History, MovePicker};
+use crate::rachel::skill::{Skill, CANDIDATES};
+use crate::rachel::time::{Limits, TimeManager};
+use crate::rachel::tt::{Bound, TranspositionTable};
+use crate::syzygy::{Tablebases, Wdl};
+use crate::uci::options::Options;
+
+mod mate;
+
+pub const MAX_PLY: usize = 128;
+/// Deepest iteration iterative deepening will start.
+pub const MAX_DEPTH: u8 = 100;
+pub const INFINITY: i32 = 32_000;
+pub const MATE: i32 = 31_000;
+/// Scores beyond this (in either direction) are mates.
+pub const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+/// A tablebase win, less the ply it's found at, so nearer ones score higher.
+/// Always below [MATE_BOUND], as it's not a mate the search has seen.
+pub const TB_WIN: i32 = MATE_BOUND - 1;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// Reverse futility pruning's margin, per ply of remaining depth.
+const RFP_MARGIN: i32 = 120;
+const RFP_MAX_DEPTH: i32 = 6;
+const NMP_MIN_DEPTH: i32 = 3;
+/// Futility pruning's margins, indexed by remaining depth.
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 350];
+const LMR_MIN_DEPTH: i32 = 3;
+/// Moves searched at full depth before late move reductions start.
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+/// The mate search for `go mate` gets this fraction of the time or nodes it's
+/// allowed, having no table to speed it up. A normal search gets the rest.
+const MATE_SEARCH_SHARE: u32 = 4;
+
+/// Toggles for the search's heuristics, so their effect can be measured.
+#[derive(Clone, Debug)]
+pub struct SearchParams {
+    /// Search the transposition table's move first.
+    pub hash_move: bool,
+    /// Order captures by [crate::rachel::move_picker::mvv_lva].
+    pub mvv_lva: bool,
+    /// Search captures which lose material by [crate::rachel::see::see] after
+    /// quiet moves, and not at all in quiescence search.
+    pub see: bool,
+    /// Search quiet moves which caused cutoffs at the same ply early.
+    pub killers: bool,
+    /// Order quiet moves by how often they've caused cutoffs anywhere.
+    pub history: bool,
+    /// Give the opponent a free move, and if they still can't get under beta,
+    /// assume a real move won't either.
+    pub null_move: bool,
+    /// Search quiet moves late in the ordering to reduced depth, unless they
+    /// surprise by beating alpha.
+    pub lmr: bool,
+    /// Cut off near the leaves when the static eval is far above beta.
+    pub reverse_futility: bool,
+    /// Skip quiet moves at frontier nodes when the static eval is so far below
+    /// alpha that they're hopeless.
+    pub futility: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            hash_move: true,
+            mvv_lva: true,
+            see: true,
+            killers: true,
+            history: true,
+            null_move: true,
+            lmr: true,
+            reverse_futility: true,
+            futility: true,
+        }
+    }
+}
+
+/// UCI option names for the pruning toggles.
+const OPT_NULL_MOVE: &str = "NullMove";
+const OPT_LMR: &str = "LMR";
+const OPT_REVERSE_FUTILITY: &str = "ReverseFutility";
+const OPT_FUTILITY: &str = "Futility";
+
+impl SearchParams {
+    /// Every pruning and reduction off, so the full tree is searched.
+    pub fn unpruned() -> SearchParams {
+        SearchParams {
+            null_move: false,
+            lmr: false,
+            reverse_futility: false,
+            futility: false,
+            ..SearchParams::default()
+        }
+    }
+
+    /// Every ordering heuristic (and pruning) off, so moves are searched in
+    /// generation order.
+    pub fn unordered() -> SearchParams {
+        SearchParams {
+            hash_move: false,
+            mvv_lva: false,
+            see: false,
+            killers: false,
+            history: false,
+            ..SearchParams::unpruned()
+        }
+    }
+
+    /// Declare the options for toggling pruning in `options`, at their
+    /// defaults, each setting its flag in the params `params` finds in `T`.
+    pub fn declare<T: 'static>(
+        options: Options<T>,
+        params: fn(&mut T) -> &mut SearchParams,
+    ) -> Options<T> {
+        let defaults = SearchParams::default();
+        let toggles: [(&str, bool, fn(&mut SearchParams) -> &mut bool); 4] = [
+            (OPT_NULL_MOVE, defaults.null_move, |p| &mut p.null_move),
+            (OPT_LMR, defaults.lmr, |p| &mut p.lmr),
+            (OPT_REVERSE_FUTILITY, defaults.reverse_futility, |p| {
+                &mut p.reverse_futility
+            }),
+            (OPT_FUTILITY, defaults.futility, |p| &mut p.futility),
+        ];
+        toggles
+            .into_iter()
+            .fold(options, |options, (name, default, flag)| {
+                options
+                    .check(name, default)
+                    .on_change(name, move |t, value| {
+                        *flag(params(t)) = value.as_check().expect("Pruning option is a check");
+                        Ok(())
+                    })
+                    .expect("Pruning option was just declared")
+            })
+    }
+}
+
+/// Whether the side to move has anything besides pawns and its king. Without,
+/// zugzwang is likely enough that null move pruning can't be trusted.
+fn has_non_pawn_material(board: &Board) -> bool {
+    let pawns_and_kings = board.pieces(Piece::Pawn) | board.pieces(Piece::King);
+    board.color_combined(board.side_to_move()) & !pawns_and_kings != EMPTY
+}
+
+/// The score for a tablebase result `ply` plies from the root. Results the
+/// fifty-move rule spoils are scored a hair either side of a draw.
+fn tablebase_score(wdl: Wdl, ply: usize) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN - ply as i32,
+        Wdl::CursedWin => 1,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -1,
+        Wdl::Loss => -TB_WIN + ply as i32,
+    }
+}
+
+/// Scores beyond this are mates or tablebase wins, which count plies from the
+/// root.
+const DECISIVE_BOUND: i32 = TB_WIN - MAX_PLY as i32;
+
+/// A mate (or tablebase win) `ply` plies from the root is scored by its
+/// distance from there, but the table must hold its distance from the position
+/// itself, which may be reached at other plies.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= DECISIVE_BOUND {
+        score + ply as i32
+    } else if score <= -DECISIVE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// The reverse of [score_to_tt].
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= DECISIVE_BOUND {
+        score - ply as i32
+    } else if score <= -DECISIVE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// How many plies to reduce the `move_number`th move (counting from zero) by.
+fn lmr_reduction(depth: i32, move_number: usize) -> i32 {
+    (0.75 + (depth as f64).ln() * (move_number as f64).ln() / 2.25) as i32
+}
+
+/// The outcome of one iteration of iterative deepening.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: u8,
+    /// Which line this is, best first, when several are searched.
+    pub multipv: usize,
+    /// Centipawns, from the perspective of the side to move.
+    pub score: i32,
+    pub nodes: u64,
+    /// Positions found in the tablebases.
+    pub tb_hits: u64,
+    pub time: Duration,
+    pub pv: Vec<ChessMove>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<ChessMove> {
+        self.pv.first().copied()
+    }
+
+    /// Moves to mate, if the score is a mate score: positive if the side to
+    /// move is mating, negative if it's being mated.
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score >= MATE_BOUND {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score <= -MATE_BOUND {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_uci(&self) -> UciMessage {
+        let score = match self.mate_in() {
+            Some(n) => UciInfoAttribute::from_mate(n as i8),
+            None => UciInfoAttribute::from_centipawns(self.score),
+        };
+        let millis = self.time.as_millis().max(1) as u64;
+        UciMessage::Info(vec![
+            UciInfoAttribute::Depth(self.depth),
+            UciInfoAttribute::MultiPv(self.multipv as u16),
+            score,
+            UciInfoAttribute::Nodes(self.nodes),
+            UciInfoAttribute::Nps(self.nodes * 1000 / millis),
+            UciInfoAttribute::TbHits(self.tb_hits),
+            UciInfoAttribute::Time(self.time),
+            UciInfoAttribute::Pv(self.pv.clone()),
+        ])
+    }
+}
+
+/// Iterative deepening, negamax alpha-beta search with a quiescence search.
+pub struct Search {
+    pub params: SearchParams,
+    /// Endgame tables to probe, once there are few enough pieces.
+    pub tablebases: Option<Arc<Tablebases>>,
+    /// Shared by every thread of the search.
+    tt: Arc<TranspositionTable>,
+    killers: [[Option<ChessMove>; 2]; MAX_PLY],
+    /// Whether the move into each ply was a null move, as two in a row would
+    /// just be searching the same position at reduced depth.
+    null_moved: [bool; MAX_PLY],
+    history: History,
+    /// Hashes of the game's earlier positions, followed by those along the line
+    /// currently being searched, for repetition detection.
+    path: Vec<u64>,
+    nodes: u64,
+    /// Nodes searched by every thread, each adding its own periodically.
+    node_counter: Arc<AtomicU64>,
+    /// How many of this thread's nodes are in `node_counter` already.
+    published_nodes: u64,
+    tb_hits: u64,
+    node_limit: Option<u64>,
+    /// What the current search was asked for, updated on `ponderhit`.
+    limits: Limits,
+    time: Option<TimeManager>,
+    /// Set once a limit is hit mid-iteration, so the search unwinds, and the
+    /// iteration's result is discarded.
+    aborted: bool,
+    /// Only these moves are searched at the root, if there are any.
+    root_moves: Vec<ChessMove>,
+    /// Root moves already taken by earlier lines of this iteration.
+    excluded_root_moves: Vec<ChessMove>,
+    /// How many of the best root moves to find lines for.
+    pub multi_pv: usize,
+    /// Threads to search with: this one, and helpers sharing its table.
+    pub threads: usize,
+    /// How well to play, when not at full strength.
+    pub skill: Skill,
+    /// The lines from the last completed iteration, best first.
+    lines: Vec<SearchInfo>,
+    /// Raised from outside to stop the search as soon as possible.
+    stop: Arc<AtomicBool>,
+    /// Raised while pondering, and lowered from outside on `ponderhit`.
+    pondering: Arc<AtomicBool>,
+}
+
+impl Search {
+    pub fn new(hash_mb: usize) -> Search {
+        Search::with_tt(Arc::new(TranspositionTable::new(hash_mb)))
+    }
+
+    fn with_tt(tt: Arc<TranspositionTable>) -> Search {
+        Search {
+            params: SearchParams::default(),
+            tablebases: None,
+            tt,
+            killers: [[None; 2]; MAX_PLY],
+            null_moved: [false; MAX_PLY],
+            history: History::new(),
+            path: Vec::new(),
+            nodes: 0,
+            node_counter: Arc::new(AtomicU64::new(0)),
+            published_nodes: 0,
+            tb_hits: 0,
+            node_limit: None,
+            limits: Limits::default(),
+            time: None,
+            aborted: false,
+            root_moves: Vec::new(),
+            excluded_root_moves: Vec::new(),
+            multi_pv: 1,
+            threads: 1,
+            skill: Skill::default(),
+            lines: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Share a flag which stops the search when raised.
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    /// Share a flag which, when lowered, converts a pondering search (one with
+    /// [Limits::ponder] set) into a normal one.
+    pub fn set_ponder_flag(&mut self, pondering: Arc<AtomicBool>) {
+        self.pondering = pondering;
+    }
+
+    /// Nodes searched by all threads.
+    pub fn nodes(&self) -> u64 {
+        self.node_counter.load(Ordering::Relaxed) + self.nodes - self.published_nodes
+    }
+
+    /// Add this thread's nodes since it last did to the shared count.
+    fn publish_nodes(&mut self) {
+        self.node_counter
+            .fetch_add(self.nodes - self.published_nodes, Ordering::Relaxed);
+        self.published_nodes = self.nodes;
+    }
+
+    /// The lines found by the last search, best first: one per
+    /// [Search::multi_pv], unless there weren't that many legal moves, or the
+    /// search was weakened, and had more to choose among.
+    pub fn lines(&self) -> &[SearchInfo] {
+        &self.lines
+    }
+
+    /// Forget everything learned from previous searches.
+    pub fn new_game(&mut self) {
+        self.tt.clear();
+        self.history.clear();
+    }
+
+    /// Search `board` within `limits`, calling `report` after each completed
+    /// iteration. `history` is the hashes of the positions played before
+    /// `board`, oldest first.
+    pub fn search<F>(
+        &mut self,
+        board: &Board,
+        history: &[u64],
+        limits: &Limits,
+        mut report: F,
+    ) -> SearchInfo
+    where
+        F: FnMut(&SearchInfo),
+    {
+        let start = Instant::now();
+        self.node_counter = Arc::new(AtomicU64::new(0));
+        self.prepare(board, history, limits);
+        self.time = Some(TimeManager::new(limits));
+        if let Some(info) = self.root_tablebase_move(board) {
+            report(&info);
+            self.time = None;
+            return info;
+        }
+        if let Some(moves) = limits.mate {
+            let time = self.time.take();
+            self.time = time.as_ref().map(|t| t.share(MATE_SEARCH_SHARE));
+            self.node_limit = limits.nodes.map(|n| n / MATE_SEARCH_SHARE as u64);
+            let pondering = self.limits.ponder;
+            let found = self.mate_search(board, moves, start);
+            // unless `ponderhit` started the clock afresh meanwhile
+            if self.limits.ponder == pondering {
+                self.time = time;
+            }
+            self.node_limit = limits.nodes;
+            if let Some(info) = found {
+                report(&info);
+                self.lines = vec![info.clone()];
+                self.time = None;
+                return info;
+            }
+            // a mate may still turn up in a normal search, which has the rest
+            // of the budget, even if the mate search ran out of its share
+            self.aborted = false;
+        }
+        // a weakened search is shallower, and has a few lines to choose among,
+        // though only as many as were asked for are reported
+        let depth_cap = self.skill.depth_cap();
+        let multi_pv = self.multi_pv;
+        let mut report = |info: &SearchInfo| {
+            if info.multipv <= multi_pv {
+                report(info)
+            }
+        };
+        if let Some(cap) = depth_cap {
+            self.limits.depth = Some(self.limits.depth.map_or(cap, |d| d.min(cap)));
+            self.multi_pv = multi_pv.max(CANDIDATES);
+        }
+        let mut last = if self.threads > 1 {
+            self.iterate_with_helpers(board, history, start, &mut report)
+        } else {
+            self.iterate(board, 1, start, &mut report)
+        };
+        if depth_cap.is_some() {
+            self.multi_pv = multi_pv;
+            if let Some(line) = self.skill.pick(&self.lines) {
+                last = line.clone();
+            }
+        }
+        if last.pv.is_empty() {
+            // not even one iteration finished, but any legal move beats none
+            let fallback = self.root_moves.first().copied();
+            if let Some(m) = fallback.or_else(|| MoveGen::new_legal(board).next()) {
+                last.pv.push(m);
+            }
+        }
+        self.time = None;
+        last
+    }
+
+    /// Reset everything particular to one search, except the clock.
+    fn prepare(&mut self, board: &Board, history: &[u64], limits: &Limits) {
+        self.nodes = 0;
+        self.published_nodes = 0;
+        self.tb_hits = 0;
+        self.node_limit = limits.nodes;
+        self.limits = limits.clone();
+        self.aborted = false;
+        self.root_moves = limits
+            .search_moves
+            .iter()
+            .copied()
+            .filter(|&m| board.legal(m))
+            .collect();
+        self.path = history.to_vec();
+        self.killers = [[None; 2]; MAX_PLY];
+        self.null_moved = [false; MAX_PLY];
+        self.lines.clear();
+    }
+
+    /// Iterative deepening from `first_depth`, until a limit is hit, calling
+    /// `report` with each line of each completed iteration. Returns the best
+    /// line of the last one, which has no moves if none completed.
+    fn iterate<F>(
+        &mut self,
+        board: &Board,
+        first_depth: u8,
+        start: Instant,
+        report: &mut F,
+    ) -> SearchInfo
+    where
+        F: FnMut(&SearchInfo),
+    {
+        let mut last = SearchInfo {
+            depth: 0,
+            multipv: 1,
+            score: 0,
+            nodes: 0,
+            tb_hits: 0,
+            time: Duration::ZERO,
+            pv: vec![],
+        };
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let root_move_count = if self.root_moves.is_empty() {
+            MoveGen::new_legal(board).len()
+        } else {
+            self.root_moves.len()
+        };
+        let line_count = self.multi_pv.clamp(1, root_move_count.max(1));
+        for depth in first_depth.min(max_depth)..=max_depth {
+            let iteration_start = start.elapsed();
+            // each line is the best root move the lines before it didn't take
+            self.excluded_root_moves.clear();
+            let mut lines = vec![];
+            for multipv in 1..=line_count {
+                let score = self.negamax(board, depth as i32, 0, -INFINITY, INFINITY);
+                if self.aborted {
+                    break;
+                }
+                let line = SearchInfo {
+                    depth,
+                    multipv,
+                    score,
+                    nodes: self.nodes(),
+                    tb_hits: self.tb_hits,
+                    time: start.elapsed(),
+                    pv: self.pv(board, depth as usize),
+                };
+                match line.best_move() {
+                    Some(m) => self.excluded_root_moves.push(m),
+                    None => break,
+                }
+                lines.push(line);
+            }
+            if lines.is_empty() {
+                break;
+            }
+            // a later line can outscore an earlier one, the TT being warmer
+            lines.sort_by_key(|l| -l.score);
+            if lines.len() > 1 {
+                // each line left its own move at the root, the last the
+                // weakest, so the best goes back, to be searched first next
+                let best = &lines[0];
+                let score = score_to_tt(best.score, 0);
+                let hash = board.get_hash();
+                self.tt
+                    .store(hash, depth as i32, score, Bound::Exact, best.best_move());
+            }
+            for (i, line) in lines.iter_mut().enumerate() {
+                line.multipv = i + 1;
+                report(line);
+            }
+            last = lines[0].clone();
+            self.lines = lines;
+            if self.aborted {
+                break;
+            }
+            if let (Some(mate), Some(n)) = (self.limits.mate, last.mate_in()) {
+                if n > 0 && n <= mate as i32 {
+                    break;
+                }
+            }
+            self.check_ponderhit();
+            // which `ponderhit` may have lowered
+            if self.limits.depth.is_some_and(|d| depth >= d) {
+                break;
+            }
+            let last_iteration = start.elapsed() - iteration_start;
+            let fits = match &self.time {
+                Some(t) => t.next_iteration_fits(last_iteration),
+                None => true,
+            };
+            if !self.limits.infinite && !fits {
+                break;
+            }
+        }
+        self.publish_nodes();
+        last
+    }
+
+    /// Lazy SMP: helper threads search the same position at the same time,
+    /// sharing nothing but the transposition table, which they fill with
+    /// results this thread then finds. Half the helpers start a ply deeper, so
+    /// the threads' iterations don't all line up. The helpers are only
+    /// stopped once this thread is done, and if one finished a deeper
+    /// iteration than it did, the helper's line is the result.
+    fn iterate_with_helpers<F>(
+        &mut self,
+        board: &Board,
+        history: &[u64],
+        start: Instant,
+        report: &mut F,
+    ) -> SearchInfo
+    where
+        F: FnMut(&SearchInfo),
+    {
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        // no clock or pondering: they search until the node limit, shared
+        // with this thread, or until told not to
+        let limits = Limits {
+            ponder: false,
+            ..self.limits.clone()
+        };
+        let mut helpers: Vec<Search> = (1..self.threads)
+            .map(|_| {
+                let mut helper = Search::with_tt(self.tt.clone());
+                helper.params = self.params.clone();
+                helper.tablebases = self.tablebases.clone();
+                helper.node_counter = self.node_counter.clone();
+                helper.stop = helpers_stop.clone();
+                helper.prepare(board, history, &limits);
+                helper
+            })
+            .collect();
+        let (main, results) = thread::scope(|s| {
+            let handles: Vec<_> = helpers
+                .iter_mut()
+                .enumerate()
+                .map(|(i, helper)| {
+                    let first_depth = 1 + (i as u8 + 1) % 2;
+                    s.spawn(move || {
+                        helper.iterate(board, first_depth, start, &mut |_: &SearchInfo| {})
+                    })
+                })
+                .collect();
+            let main = self.iterate(board, 1, start, report);
+            helpers_stop.store(true, Ordering::Relaxed);
+            let results: Vec<SearchInfo> = handles
+                .into_iter()
+                .map(|h| h.join().expect("Helper search panicked"))
+                .collect();
+            (main, results)
+        });
+        if self.multi_pv > 1 {
+            // only this thread searched every line
+            return main;
+        }
+        let deepest = results
+            .into_iter()
+            .filter(|r| !r.pv.is_empty())
+            .max_by_key(|r| r.depth);
+        match deepest {
+            Some(r) if r.depth > main.depth => {
+                let best = SearchInfo {
+                    nodes: self.nodes(),
+                    time: start.elapsed(),
+                    ..r
+                };
+                report(&best);
+                self.lines = vec![best.clone()];
+                best
+            }
+            _ => main,
+        }
+    }
+
+    /// At a tablebase root, there's nothing to search: the tables' move keeps
+    /// the result. Not when only some moves may be searched, though, as the
+    /// tables' move mightn't be one of them, nor when several lines are
+    /// wanted.
+    fn root_tablebase_move(&mut self, board: &Board) -> Option<SearchInfo> {
+        if !self.root_moves.is_empty() || self.multi_pv > 1 {
+            return None;
+        }
+        let (m, wdl) = self.tablebases.as_ref()?.best_move(board)?;
+        self.tb_hits = 1;
+        Some(SearchInfo {
+            depth: 1,
+            multipv: 1,
+            score: tablebase_score(wdl, 0),
+            nodes: 0,
+            tb_hits: 1,
+            time: Duration::ZERO,
+            pv: vec![m],
+        })
+    }
+
+    /// If a pondering search has seen `ponderhit`, start the clock, or if
+    /// there's none, fall back to the default depth.
+    fn check_ponderhit(&mut self) {
+        if self.limits.ponder && !self.pondering.load(Ordering::Relaxed) {
+            self.limits.ponder = false;
+            self.limits.bound();
+            self.time = Some(TimeManager::new(&self.limits));
+        }
+    }
+
+    /// Whether the search has been stopped, or a node or time limit reached.
+    /// The clock is only checked periodically, as it's comparatively slow.
+    fn should_abort(&mut self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        // every thread's nodes count, though the others' only as of when
+        // they were last published
+        if self.node_limit.is_some_and(|n| self.nodes() >= n) {
+            return true;
+        }
+        if self.nodes % 1024 != 0 {
+            return false;
+        }
+        self.publish_nodes();
+        self.check_ponderhit();
+        self.time.as_ref().is_some_and(|t| t.hard_expired())
+    }
+
+    /// Walk the transposition table's best moves from `board`.
+    fn pv(&self, board: &Board, max_len: usize) -> Vec<ChessMove> {
+        let mut pv = vec![];
+        let mut seen = vec![];
+        let mut board = *board;
+        while pv.len() < max_len {
+            let hash = board.get_hash();
+            if seen.contains(&hash) {
+                break;
+            }
+            seen.push(hash);
+            match self.tt.probe(hash).and_then(|e| e.best_move) {
+                Some(m) if board.legal(m) => {
+                    pv.push(m);
+                    board = board.make_move_new(m);
+                }
+                _ => break,
+            }
+        }
+        pv
+    }
+
+    fn negamax(&mut self, board: &Board, depth: i32, ply: usize, alpha: i32, beta: i32) -> i32 {
+        if depth <= 0 {
+            return self.quiesce(board, ply, alpha, beta);
+        }
+        let hash = board.get_hash();
+        if ply > 0 && self.path.contains(&hash) {
+            return 0;
+        }
+        self.path.push(hash);
+        let score = self.negamax_inner(board, hash, depth, ply, alpha, beta);
+        self.path.pop();
+        score
+    }
+
+    fn negamax_inner(
+        &mut self,
+        board: &Board,
+        hash: u64,
+        depth: i32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.aborted || self.should_abort() {
+            self.aborted = true;
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(board);
+        }
+
+        let tt_entry = self.tt.probe(hash);
+        if let Some(e) = tt_entry {
+            let score = score_from_tt(e.score, ply);
+            if ply > 0 && e.depth >= depth {
+                match e.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        if ply > 0 {
+            let wdl = self.tablebases.as_ref().and_then(|tb| tb.probe_wdl(board));
+            if let Some(wdl) = wdl {
+                self.tb_hits += 1;
+                let score = tablebase_score(wdl, ply);
+                self.tt
+                    .store(hash, depth, score_to_tt(score, ply), Bound::Exact, None);
+                return score;
+            }
+        }
+
+        let in_check = *board.checkers() != EMPTY;
+        let pv_node = beta - alpha > 1;
+        let static_eval = if in_check { -INFINITY } else { evaluate(board) };
+
+        if !pv_node && !in_check {
+            if self.params.reverse_futility
+                && depth <= RFP_MAX_DEPTH
+                && beta.abs() < MATE_BOUND
+                && static_eval - RFP_MARGIN * depth >= beta
+            {
+                return static_eval;
+            }
+
+            if self.params.null_move
+                && depth >= NMP_MIN_DEPTH
+                && ply > 0
+                && !self.null_moved[ply]
+                && static_eval >= beta
+                && beta.abs() < MATE_BOUND
+                && has_non_pawn_material(board)
+            {
+                if let Some(child) = board.null_move() {
+                    let r = 2 + depth / 4;
+                    self.null_moved[ply + 1] = true;
+                    let score = -self.negamax(&child, depth - 1 - r, ply + 1, -beta, -beta + 1);
+                    self.null_moved[ply + 1] = false;
+                    if score >= beta {
+                        // an unproven mate isn't to be trusted
+                        return if score >= MATE_BOUND { beta } else { score };
+                    }
+                }
+            }
+        }
+
+        let futile = self.params.futility
+            && !pv_node
+            && !in_check
+            && depth < FUTILITY_MARGINS.len() as i32
+            && alpha.abs() < MATE_BOUND
+            && static_eval + FUTILITY_MARGINS[depth as usize] <= alpha;
+
+        let picker = MovePicker::new(
+            board,
+            tt_entry.and_then(|e| e.best_move),
+            &self.killers[ply],
+            &self.history,
+            &self.params,
+        );
+        let alpha_orig = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        // of the moves searched, so those skipped at the root don't count
+        let mut searched = 0;
+        for m in picker {
+            if ply == 0
+                && ((!self.root_moves.is_empty() && !self.root_moves.contains(&m))
+                    || self.excluded_root_moves.contains(&m))
+            {
+                continue;
+            }
+            let child = board.make_move_new(m);
+            let quiet = is_quiet(board, m);
+            let gives_check = *child.checkers() != EMPTY;
+            if futile && searched > 0 && quiet && !gives_check {
+                continue;
+            }
+            let i = searched;
+            searched += 1;
+            let score = if i == 0 {
+                -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                // principal variation search: prove the move is no better than
+                // alpha with a null window, and only search it properly if not
+                let mut r = 0;
+                if self.params.lmr
+                    && depth >= LMR_MIN_DEPTH
+                    && i >= LMR_FULL_DEPTH_MOVES
+                    && quiet
+                    && !in_check
+                    && !gives_check
+                {
+                    r = lmr_reduction(depth, i) - pv_node as i32;
+                    r = r.clamp(0, depth - 2);
+                }
+                let mut score = -self.negamax(&child, depth - 1 - r, ply + 1, -alpha - 1, -alpha);
+                if score > alpha && r > 0 {
+                    score = -self.negamax(&child, depth - 1, ply + 1, -alpha - 1, -alpha);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha);
+                }
+                score
+            };
+            if self.aborted {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m);
+                if score > alpha {
+                    alpha = score;
+                }
+            }
+            if alpha >= beta {
+                if quiet {
+                    self.record_cutoff(m, depth, ply);
+                }
+                break;
+            }
+        }
+
+        if best_move.is_none() {
+            // no legal moves
+            return if *board.checkers() == EMPTY {
+                0
+            } else {
+                -MATE + ply as i32
+            };
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > alpha_orig {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt
+            .store(hash, depth, score_to_tt(best_score, ply), bound, best_move);
+        best_score
+    }
+
+    fn record_cutoff(&mut self, m: ChessMove, depth: i32, ply: usize) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+        self.history.reward(m, depth);
+    }
+
+    fn quiesce(&mut self, board: &Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.aborted || self.should_abort() {
+            self.aborted = true;
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(board);
+        }
+        let in_check = *board.checkers() != EMPTY;
+        let mut best_score;
+        let picker = if in_check {
+            // every evasion must be considered, or a mate could be missed
+            best_score = -MATE + ply as i32;
+            MovePicker::new(board, None, &[None; 2], &self.history, &self.params)
+        } else {
+            let stand_pat = evaluate(board);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+            MovePicker::noisy(board, &self.params)
+        };
+        for m in picker {
+            let child = board.make_move_new(m);
+            let score = -self.quiesce(&child, ply + 1, -beta, -alpha);
+            if self.aborted {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    if score >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best_score
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove};
+
+    use super::*;
+
+    fn search(fen: &str, depth: u8, params: SearchParams) -> SearchInfo {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        search.params = params;
+        search.search(
+            &Board::from_str(fen).unwrap(),
+            &[],
+            &Limits::depth(depth),
+            |_| {},
+        )
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let info = search(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            3,
+            SearchParams::default(),
+        );
+        assert_eq!(ChessMove::from_str("a1a8").ok(), info.best_move());
+        assert_eq!(Some(1), info.mate_in());
+    }
+
+    #[test]
+    fn takes_hanging_queen() {
+        let info = search(
+            "4k3/8/8/3q4/8/8/3R4/3K4 w - - 0 1",
+            2,
+            SearchParams::default(),
+        );
+        assert_eq!(ChessMove::from_str("d2d5").ok(), info.best_move());
+    }
+
+    /// Node counts with and without move ordering, printed so the reduction
+    /// can be seen with `cargo test -- --nocapture`.
+    #[test]
+    fn ordering_reduces_nodes() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        ];
+        for fen in positions {
+            let unordered = search(fen, 4, SearchParams::unordered()).nodes;
+            let ordered = search(fen, 4, SearchParams::unpruned()).nodes;
+            println!("{unordered:>10} -> {ordered:>10} nodes for {fen}");
+            assert!(
+                ordered < unordered,
+                "ordering searched {ordered} nodes, vs {unordered} without, for {fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn pruning_reduces_nodes() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ];
+        for fen in positions {
+            let unpruned = search(fen, 5, SearchParams::unpruned()).nodes;
+            let pruned = search(fen, 5, SearchParams::default()).nodes;
+            println!("{unpruned:>10} -> {pruned:>10} nodes for {fen}");
+            assert!(pruned < unpruned, "pruned {pruned} vs {unpruned} for {fen}");
+        }
+    }
+
+    #[test]
+    fn pruning_keeps_mates() {
+        let info = search(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            5,
+            SearchParams::default(),
+        );
+        assert_eq!(ChessMove::from_str("a1a8").ok(), info.best_move());
+        assert_eq!(Some(1), info.mate_in());
+    }
+
+    #[test]
+    fn options() {
+        let mut params = SearchParams::default();
+        let mut options = SearchParams::declare(Options::new(), |p| p);
+        assert_eq!(4, options.uci_options().len());
+        for (name, value) in [("nullmove", "false"), ("LMR", "false")] {
+            assert_eq!(Ok(true), options.set(&mut params, name, Some(value)));
+        }
+        assert!(!params.null_move);
+        assert!(!params.lmr);
+        assert!(params.futility);
+        assert_eq!(Ok(false), options.set(&mut params, "Hash", Some("1")));
+    }
+
+    #[test]
+    fn node_limit() {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let limits = Limits {
+            nodes: Some(5_000),
+            ..Limits::default()
+        };
+        let info = search.search(&Board::default(), &[], &limits, |_| {});
+        assert!(info.best_move().is_some());
+        assert!(search.nodes() <= 5_000);
+        // between them, not each
+        search.threads = 4;
+        search.search(&Board::default(), &[], &limits, |_| {});
+        assert!(search.nodes() < 5_000 + 4 * 1024, "{}", search.nodes());
+    }
+
+    #[test]
+    fn search_moves() {
+        // taking the queen is clearly best, but only a pawn push is allowed
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let limits = Limits {
+            depth: Some(3),
+            search_moves: vec![ChessMove::from_str("a2a3").unwrap()],
+            ..Limits::default()
+        };
+        let board = Board::from_str("4k3/8/8/3q4/8/8/P2R4/3K4 w - - 0 1").unwrap();
+        let info = search.search(&board, &[], &limits, |_| {});
+        assert_eq!(ChessMove::from_str("a2a3").ok(), info.best_move());
+    }
+
+    #[test]
+    fn multi_pv() {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        search.multi_pv = 3;
+        // taking the queen with the pawn is best, then with the rook
+        let board = Board::from_str("4k3/8/4p3/3q4/4P3/8/8/3RK3 w - - 0 1").unwrap();
+        let mut reported = vec![];
+        let info = search.search(&board, &[], &Limits::depth(3), |i| {
+            reported.push((i.depth, i.multipv))
+        });
+        assert_eq!(ChessMove::from_str("e4d5").ok(), info.best_move());
+        let lines = search.lines();
+        assert_eq!(3, lines.len());
+        assert_eq!(ChessMove::from_str("d1d5").ok(), lines[1].best_move());
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_ne!(lines[1].best_move(), lines[2].best_move());
+        assert_eq!(vec![(3, 1), (3, 2), (3, 3)], reported[6..]);
+        // the root's left with the best line's move, not the last line's
+        let root = search.tt.probe(board.get_hash()).unwrap();
+        assert_eq!(info.best_move(), root.best_move);
+
+        // fewer lines than asked for, with only three legal moves
+        search.multi_pv = 5;
+        let board = Board::from_str("k7/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        search.search(&board, &[], &Limits::depth(2), |_| {});
+        assert_eq!(3, search.lines().len());
+    }
+
+    /// Qxf7 is mate.
+    const SCHOLARS_MATE: &str =
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+
+    #[test]
+    fn one_thread_is_deterministic() {
+        let board = Board::from_str(SCHOLARS_MATE).unwrap();
+        let run = || {
+            let mut search = Search::new(DEFAULT_HASH_MB);
+            search.threads = 1;
+            let info = search.search(&board, &[], &Limits::depth(4), |_| {});
+            (search.nodes(), info.score, info.pv)
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn helper_threads() {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        search.threads = 4;
+        let board = Board::from_str(SCHOLARS_MATE).unwrap();
+        let info = search.search(&board, &[], &Limits::depth(4), |_| {});
+        assert_eq!(ChessMove::from_str("h5f7").ok(), info.best_move());
+        assert_eq!(Some(1), info.mate_in());
+    }
+
+    #[test]
+    fn weakened() {
+        let board = Board::from_str("4k3/8/4p3/3q4/4P3/8/8/3RK3 w - - 0 1").unwrap();
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let mut options = Skill::declare(Options::new(), |s: &mut Search| &mut s.skill);
+        options.set(&mut search, "Skill Level", Some("0")).unwrap();
+        search.skill.seed(1);
+        let mut depths = vec![];
+        let mut multipvs = vec![];
+        let mut moves = vec![];
+        for _ in 0..20 {
+            let info = search.search(&board, &[], &Limits::depth(6), |i| {
+                depths.push(i.depth);
+                multipvs.push(i.multipv);
+            });
+            moves.push(info.best_move().unwrap());
+            assert_eq!(4, search.lines().len());
+        }
+        assert_eq!(Some(&1), depths.iter().max());
+        // the candidates are searched, but not reported
+        assert!(multipvs.iter().all(|&n| n == 1));
+        // not always the queen capture
+        let best = ChessMove::from_str("e4d5").unwrap();
+        assert!(moves.iter().any(|&m| m != best));
+        assert_eq!(1, search.multi_pv);
+    }
+
+    #[test]
+    fn go_mate() {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let limits = Limits {
+            mate: Some(1),
+            ..Limits::default()
+        };
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let info = search.search(&board, &[], &limits, |_| {});
+        assert_eq!(Some(1), info.mate_in());
+        assert!(info.depth <= 2);
+    }
+
+    #[test]
+    fn go_mate_out_of_nodes() {
+        // no mate, and too many nodes to rule one out, but the queen's there
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let limits = Limits {
+            mate: Some(5),
+            nodes: Some(20_000),
+            ..Limits::default()
+        };
+        let board = Board::from_str("4k3/8/8/3q4/8/8/3R4/3K4 w - - 0 1").unwrap();
+        let info = search.search(&board, &[], &limits, |_| {});
+        assert_eq!(ChessMove::from_str("d2d5").ok(), info.best_move());
+        assert!(info.depth > 0);
+        assert!(search.nodes() <= 20_000);
+    }
+
+    #[test]
+    fn mate_scores_through_tt() {
+        // mate in two, and the defender's view after the first move
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let board = Board::from_str("7k/8/8/8/8/8/R7/1R5K w - - 0 1").unwrap();
+        let info = search.search(&board, &[], &Limits::depth(6), |_| {});
+        assert_eq!(Some(2), info.mate_in());
+        let board = board.make_move_new(ChessMove::from_str("b1b7").unwrap());
+        let info = search.search(&board, &[], &Limits::depth(6), |_| {});
+        assert_eq!(Some(-1), info.mate_in());
+    }
+
+    #[test]
+    fn movetime() {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let limits = Limits {
+            movetime: Some(Duration::from_millis(200)),
+            ..Limits::default()
+        };
+        let start = Instant::now();
+        let info = search.search(&Board::default(), &[], &limits, |_| {});
+        assert!(info.best_move().is_some());
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Instant;
+
+use chess::{Board, ChessMove, MoveGen, EMPTY};
+
+use crate::rachel::search::{Search, SearchInfo, MATE};
+
+impl Search {
+    /// Look for a forced mate in at most `max_moves` moves, for `go mate`.
+    /// Unlike the main search, there's no evaluation: the side to move tries
+    /// every move (checks first, and only checks for its last), and each is
+    /// refuted unless every defence is mated in time. Shorter mates are tried
+    /// first, so the first found is the quickest.
+    pub(super) fn mate_search(
+        &mut self,
+        board: &Board,
+        max_moves: u8,
+        start: Instant,
+    ) -> Option<SearchInfo> {
+        for moves in 1..=max_moves {
+            let pv = self.attack(board, moves, 0);
+            if self.aborted {
+                return None;
+            }
+            if let Some(pv) = pv {
+                let plies = 2 * moves as i32 - 1;
+                return Some(SearchInfo {
+                    depth: plies as u8,
+                    multipv: 1,
+                    score: MATE - plies,
+                    nodes: self.nodes(),
+                    tb_hits: self.tb_hits,
+                    time: start.elapsed(),
+                    pv,
+                });
+            }
+        }
+        None
+    }
+
+    /// The line by which the side to move mates within `moves` moves, against
+    /// the longest defence, if it can.
+    fn attack(&mut self, board: &Board, moves: u8, ply: usize) -> Option<Vec<ChessMove>> {
+        self.nodes += 1;
+        if self.aborted || self.should_abort() {
+            self.aborted = true;
+            return None;
+        }
+        let mut checks = vec![];
+        let mut quiets = vec![];
+        for m in MoveGen::new_legal(board) {
+            if ply == 0 && !self.root_moves.is_empty() && !self.root_moves.contains(&m) {
+                continue;
+            }
+            let child = board.make_move_new(m);
+            if *child.checkers() != EMPTY {
+                checks.push((m, child));
+            } else if moves > 1 {
+                quiets.push((m, child));
+            }
+        }
+        for (m, child) in checks.into_iter().chain(quiets) {
+            if let Some(mut line) = self.defend(&child, moves, ply + 1) {
+                line.insert(0, m);
+                return Some(line);
+            }
+            if self.aborted {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// The longest line by which the side to move, having just been given the
+    /// move which started the attacker's `moves`, is mated, if every defence
+    /// is.
+    fn defend(&mut self, board: &Board, moves: u8, ply: usize) -> Option<Vec<ChessMove>> {
+        self.nodes += 1;
+        if self.aborted || self.should_abort() {
+            self.aborted = true;
+            return None;
+        }
+        let defences = MoveGen::new_legal(board);
+        if defences.len() == 0 {
+            // mated, unless it's stalemate
+            return (*board.checkers() != EMPTY).then(Vec::new);
+        }
+        if moves == 1 {
+            return None;
+        }
+        let mut longest: Option<Vec<ChessMove>> = None;
+        for m in defences {
+            let mut line = self.attack(&board.make_move_new(m), moves - 1, ply + 1)?;
+            line.insert(0, m);
+            if line.len() > longest.as_ref().map_or(0, Vec::len) {
+                longest = Some(line);
+            }
+        }
+        longest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use crate::rachel::search::DEFAULT_HASH_MB;
+    use crate::rachel::time::Limits;
+
+    use super::*;
+
+    /// Positions, with the number of moves they're mate in, and the first
+    /// move, where only one mates that quickly.
+    const SUITE: [(&str, u8, Option<&str>); 9] = [
+        // back rank
+        ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1, Some("a1a8")),
+        // scholar's mate
+        (
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            1,
+            Some("h5f7"),
+        ),
+        // rook roller, which needs a quiet first move
+        ("7k/8/8/8/8/8/R7/1R5K w - - 0 1", 2, None),
+        // the same, for black
+        ("1r5k/r7/8/8/8/8/8/7K b - - 0 1", 2, None),
+        // the knight heads for f5 to cover g7, with black's pawns in the way
+        ("1K5k/3p4/8/3p4/8/8/4N3/6Q1 w - - 0 1", 3, Some("e2d4")),
+        // black's best defence is c1=N+; queening gets mated a move sooner, so
+        // a search that only tries queen promotions finds a mate in 2
+        ("8/8/8/3R4/3B4/3K4/2p5/4k3 w - - 0 1", 3, Some("d5c5")),
+        // quiet king moves that let black queen, and take the queen after
+        ("7k/8/4K3/8/8/8/4p3/R6N w - - 0 1", 4, Some("e6f7")),
+        ("8/8/7R/kN6/3K4/8/7p/8 w - - 0 1", 4, Some("d4c5")),
+        // king and rook against king, where only Kb5 is fast enough
+        ("k7/8/8/8/2K5/8/8/7R w - - 0 1", 5, Some("c4b5")),
+    ];
+
+    fn mate_search(fen: &str, max_moves: u8) -> Option<SearchInfo> {
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        search.prepare(&Board::from_str(fen).unwrap(), &[], &Limits::default());
+        search.mate_search(&Board::from_str(fen).unwrap(), max_moves, Instant::now())
+    }
+
+    #[test]
+    fn suite() {
+        for (fen, moves, first) in SUITE {
+            let info = mate_search(fen, 5).unwrap_or_else(|| panic!("no mate in {fen}"));
+            assert_eq!(Some(moves as i32), info.mate_in(), "{fen}");
+            if let Some(first) = first {
+                assert_eq!(ChessMove::from_str(first).ok(), info.best_move(), "{fen}");
+            }
+            assert_eq!(2 * moves as usize - 1, info.pv.len(), "{fen}");
+            if moves > 1 {
+                assert!(mate_search(fen, moves - 1).is_none(), "{fen}");
+            }
+        }
+    }
+
+    #[test]
+    fn longer_mate() {
+        // rook roller from further out
+        let info = mate_search("8/7k/8/8/8/8/R7/1R4K1 w - - 0 1", 3).unwrap();
+        assert!(info.mate_in().is_some_and(|n| n <= 3));
+    }
+
+    #[test]
+    fn no_mate() {
+        // Qf7 stalemates, which isn't a mate
+        assert!(mate_search("7k/8/6Q1/8/8/8/8/K7 w - - 0 1", 2).is_none());
+        assert!(mate_search("4k3/8/8/8/8/8/8/4K3 w - - 0 1", 3).is_none());
+    }
+}
@@ -0,0 +1,248 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use vampirc_uci::UciMessage;
+
+use crate::rachel::search::Search;
+use crate::rachel::time::Limits;
+use crate::rachel::GameState;
+use crate::uci::UciSender;
+
+/// Runs each search on its own thread, so the UCI message loop is free to
+/// process `stop`, `isready` and `quit` while it's underway. The [Search] (and
+/// its transposition table) moves to the thread for the duration, and comes
+/// back when it's joined.
+pub struct SearchThread {
+    stop: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+    idle: Option<Search>,
+    running: Option<JoinHandle<Search>>,
+}
+
+impl SearchThread {
+    pub fn new(search: Search) -> SearchThread {
+        SearchThread {
+            stop: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
+            idle: Some(search),
+            running: None,
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.running.as_ref().is_some_and(|h| !h.is_finished())
+    }
+
+    /// The search, for configuring between searches. If one is underway, it's
+    /// stopped (and reports its best move) first.
+    pub fn search_mut(&mut self) -> &mut Search {
+        self.stop();
+        self.idle.as_mut().expect("Search should be idle")
+    }
+
+    /// Start searching `state`, sending `info` as it goes and `bestmove` when
+    /// done through `sender`. An infinite search won't send `bestmove` until
+    /// it's stopped, even if it runs out of depth, nor will a pondering search
+    /// until it's stopped or sees [SearchThread::ponderhit].
+    pub fn start(&mut self, state: GameState, limits: Limits, sender: UciSender) {
+        self.stop();
+        let mut search = self.idle.take().expect("Search should be idle");
+        self.stop.store(false, Ordering::SeqCst);
+        self.pondering.store(limits.ponder, Ordering::SeqCst);
+        let stop = self.stop.clone();
+        let pondering = self.pondering.clone();
+        search.set_stop_flag(stop.clone());
+        search.set_ponder_flag(pondering.clone());
+        self.running = Some(thread::spawn(move || {
+            let info = search.search(&state.board, &state.history, &limits, |info| {
+                // if the GUI's gone, the message loop is on its way out too
+                let _ = sender.unbounded_send(info.to_uci());
+            });
+            while !stop.load(Ordering::SeqCst)
+                && (limits.infinite || pondering.load(Ordering::SeqCst))
+            {
+                thread::park();
+            }
+            let msg = match (info.best_move(), info.pv.get(1)) {
+                (Some(m), Some(&p)) => UciMessage::best_move_with_ponder(m, p),
+                (Some(m), None) => UciMessage::best_move(m),
+                // no legal moves; UCI's null move is the only option
+                (None, _) => UciMessage::Unknown("bestmove 0000".to_string(), None),
+            };
+            let _ = sender.unbounded_send(msg);
+            search
+        }));
+    }
+
+    /// Run `job` on the thread in place of a search, as for `bench` and
+    /// `perft`, and send the lines it returns through `sender`. `job` should
+    /// return early once the flag it's given is raised, by [SearchThread::stop];
+    /// what it returns then isn't sent.
+    pub fn run<F>(&mut self, sender: UciSender, job: F)
+    where
+        F: FnOnce(&Arc<AtomicBool>) -> Vec<String> + Send + 'static,
+    {
+        self.stop();
+        let search = self.idle.take().expect("Search should be idle");
+        self.stop.store(false, Ordering::SeqCst);
+        let stop = self.stop.clone();
+        self.running = Some(thread::spawn(move || {
+            let lines = job(&stop);
+            if !stop.load(Ordering::SeqCst) {
+                for line in lines {
+                    let _ = sender.unbounded_send(UciMessage::Unknown(line, None));
+                }
+            }
+            search
+        }));
+    }
+
+    /// The opponent played the expected move, so a pondering search becomes a
+    /// normal one, with its clock starting now.
+    pub fn ponderhit(&mut self) {
+        self.pondering.store(false, Ordering::SeqCst);
+        if let Some(handle) = &self.running {
+            handle.thread().unpark();
+        }
+    }
+
+    /// Stop the current search, if any, returning once it has sent `bestmove`.
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.running.take() {
+            self.stop.store(true, Ordering::SeqCst);
+            handle.thread().unpark();
+            self.idle = Some(handle.join().expect("Search thread panicked"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
+
+    use vampirc_uci::UciMessage;
+
+    use crate::rachel::search::DEFAULT_HASH_MB;
+    use crate::uci::new_channel;
+
+    use super::*;
+
+    #[test]
+    fn stop_infinite_search() {
+        let (tx, mut rx) = new_channel();
+        let mut thread = SearchThread::new(Search::new(DEFAULT_HASH_MB));
+        let limits = Limits {
+            infinite: true,
+            ..Limits::default()
+        };
+        thread.start(GameState::default(), limits, tx);
+        sleep(Duration::from_millis(100));
+        assert!(thread.is_searching());
+        let start = Instant::now();
+        thread.stop();
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert!(!thread.is_searching());
+        let mut got_best_move = false;
+        while let Ok(Some(msg)) = rx.try_next() {
+            got_best_move |= matches!(msg, UciMessage::BestMove { .. });
+        }
+        assert!(got_best_move);
+    }
+
+    #[test]
+    fn ponder_until_ponderhit() {
+        let (tx, mut rx) = new_channel();
+        let mut thread = SearchThread::new(Search::new(DEFAULT_HASH_MB));
+        let limits = Limits {
+            ponder: true,
+            depth: Some(2),
+            ..Limits::default()
+        };
+        thread.start(GameState::default(), limits, tx);
+        sleep(Duration::from_millis(200));
+        // done with depth two, but waiting
+        assert!(thread.is_searching());
+        while let Ok(Some(msg)) = rx.try_next() {
+            assert!(!matches!(msg, UciMessage::BestMove { .. }));
+        }
+        thread.ponderhit();
+        let start = Instant::now();
+        while thread.is_searching() && start.elapsed() < Duration::from_secs(1) {
+            sleep(Duration::from_millis(5));
+        }
+        assert!(!thread.is_searching());
+        let mut ponder = None;
+        while let Ok(Some(msg)) = rx.try_next() {
+            if let UciMessage::BestMove { ponder: p, .. } = msg {
+                ponder = p;
+            }
+        }
+        assert!(ponder.is_some());
+    }
+
+    #[test]
+    fn clock_starts_at_ponderhit() {
+        let (tx, _rx) = new_channel();
+        let mut thread = SearchThread::new(Search::new(DEFAULT_HASH_MB));
+        let limits = Limits {
+            ponder: true,
+            time_left: Some(Duration::from_millis(600)),
+            ..Limits::default()
+        };
+        thread.start(GameState::default(), limits, tx);
+        // longer than the clock would allow, but it isn't running yet
+        sleep(Duration::from_millis(700));
+        assert!(thread.is_searching());
+        thread.ponderhit();
+        let start = Instant::now();
+        while thread.is_searching() && start.elapsed() < Duration::from_secs(2) {
+            sleep(Duration::from_millis(5));
+        }
+        assert!(!thread.is_searching());
+    }
+
+    #[test]
+    fn finite_search_finishes_alone() {
+        let (tx, mut rx) = new_channel();
+        let mut thread = SearchThread::new(Search::new(DEFAULT_HASH_MB));
+        thread.start(GameState::default(), Limits::depth(2), tx);
+        let start = Instant::now();
+        while thread.is_searching() && start.elapsed() < Duration::from_secs(10) {
+            sleep(Duration::from_millis(5));
+        }
+        let mut got_best_move = false;
+        while let Ok(Some(msg)) = rx.try_next() {
+            got_best_move |= matches!(msg, UciMessage::BestMove { .. });
+        }
+        assert!(got_best_move);
+        // the search is available again
+        thread.search_mut().new_game();
+    }
+
+    #[test]
+    fn stop_job() {
+        let (tx, mut rx) = new_channel();
+        let mut thread = SearchThread::new(Search::new(DEFAULT_HASH_MB));
+        thread.run(tx.clone(), |_| vec!["done".to_string()]);
+        thread.stop();
+        assert_eq!(
+            Ok(Some(UciMessage::Unknown("done".to_string(), None))),
+            rx.try_next().map_err(drop)
+        );
+
+        thread.run(tx, |stop| {
+            while !stop.load(Ordering::SeqCst) {
+                sleep(Duration::from_millis(5));
+            }
+            vec!["stopped".to_string()]
+        });
+        sleep(Duration::from_millis(50));
+        assert!(thread.is_searching());
+        thread.stop();
+        // nothing sent, and the job's sender is gone
+        assert!(matches!(rx.try_next(), Ok(None)));
+        thread.search_mut().new_game();
+    }
+}
@@ -0,0 +1,156 @@
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, ChessMove, Color, Piece, Rank, Square, ALL_PIECES, EMPTY,
+};
+
+use crate::rachel::eval::piece_value;
+use crate::rachel::move_picker::captured_piece;
+
+/// Every piece, of either color, attacking `sq`, given `occupied` squares. Any
+/// pieces no longer in `occupied` are excluded, so sliders behind them (the
+/// "x-ray" attackers) are found as pieces are traded off.
+pub fn attackers_to(board: &Board, sq: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn);
+    let bishops = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let rooks = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    let attackers = (get_knight_moves(sq) & board.pieces(Piece::Knight))
+        | (get_king_moves(sq) & board.pieces(Piece::King))
+        | (get_bishop_moves(sq, occupied) & bishops)
+        | (get_rook_moves(sq, occupied) & rooks)
+        // a pawn attacks sq from wherever an opposing pawn on sq would attack
+        | get_pawn_attacks(sq, Color::Black, pawns & board.color_combined(Color::White))
+        | get_pawn_attacks(sq, Color::White, pawns & board.color_combined(Color::Black));
+    attackers & occupied
+}
+
+pub(crate) fn least_valuable(board: &Board, attackers: BitBoard) -> Option<(Square, Piece)> {
+    ALL_PIECES.iter().find_map(|&p| {
+        let bb = attackers & board.pieces(p);
+        if bb == EMPTY {
+            None
+        } else {
+            Some((bb.to_square(), p))
+        }
+    })
+}
+
+fn promotes(piece: Piece, sq: Square) -> bool {
+    piece == Piece::Pawn && (sq.get_rank() == Rank::First || sq.get_rank() == Rank::Eighth)
+}
+
+/// Static exchange evaluation: the material `m`'s side can expect to win (or
+/// lose, if negative) if both sides keep capturing on its destination square
+/// with their least valuable piece, for as long as it's profitable. Attackers
+/// behind other attackers are included; pins are not considered.
+pub fn see(board: &Board, m: ChessMove) -> i32 {
+    let from = m.get_source();
+    let to = m.get_dest();
+    let Some(mover) = board.piece_on(from) else {
+        return 0;
+    };
+    let mut gain = vec![captured_piece(board, m).map_or(0, piece_value)];
+    if mover == Piece::King {
+        // it's legal, so it can't be recaptured
+        return gain[0];
+    }
+    let mut on_square = piece_value(mover);
+    if let Some(promo) = m.get_promotion() {
+        gain[0] += piece_value(promo) - piece_value(Piece::Pawn);
+        on_square = piece_value(promo);
+    }
+
+    let mut occupied = board.combined() ^ BitBoard::from_square(from);
+    if mover == Piece::Pawn && board.piece_on(to).is_none() && from.get_file() != to.get_file() {
+        // en passant: the captured pawn is beside, not on, the destination
+        occupied ^= BitBoard::from_square(Square::make_square(from.get_rank(), to.get_file()));
+    }
+    let mut attackers = attackers_to(board, to, occupied);
+    let mut side = !board.side_to_move();
+    while let Some((sq, piece)) = least_valuable(board, attackers & board.color_combined(side)) {
+        let next_occupied = occupied ^ BitBoard::from_square(sq);
+        let next_attackers = attackers_to(board, to, next_occupied);
+        if piece == Piece::King && next_attackers & board.color_combined(!side) != EMPTY {
+            // the king may only recapture on an undefended square
+            break;
+        }
+        let mut g = on_square - gain[gain.len() - 1];
+        on_square = piece_value(piece);
+        if promotes(piece, to) {
+            g += piece_value(Piece::Queen) - piece_value(Piece::Pawn);
+            on_square = piece_value(Piece::Queen);
+        }
+        gain.push(g);
+        occupied = next_occupied;
+        attackers = next_attackers;
+        side = !side;
+    }
+
+    // each side may decline to continue the exchange, if that's better for it
+    while gain.len() > 1 {
+        let last = gain.pop().unwrap();
+        let prev = gain.last_mut().unwrap();
+        *prev = -(-*prev).max(last);
+    }
+    gain[0]
+}
+
+/// Whether [see] of `m` is at least `threshold`.
+pub fn see_ge(board: &Board, m: ChessMove, threshold: i32) -> bool {
+    see(board, m) >= threshold
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove};
+
+    use super::*;
+
+    /// Positions, moves, and expected exchange results, using the values in
+    /// [crate::rachel::eval::PIECE_VALUES].
+    const SUITE: [(&str, &str, i32); 11] = [
+        // undefended pawn
+        (
+            "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+            "e1e5",
+            100,
+        ),
+        // knight for a pawn, despite white's rook and queen behind it
+        (
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "d3e5",
+            -220,
+        ),
+        // the second rook recaptures through the first
+        ("3rk3/8/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5", 100),
+        // without the second rook, the first is lost for a pawn
+        ("3rk3/8/8/3p4/8/8/3R4/6K1 w - - 0 1", "d2d5", -400),
+        // quiet move onto a square a pawn attacks
+        ("4k3/8/8/2p5/8/3N4/8/4K3 w - - 0 1", "d3b4", -320),
+        // en passant
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100),
+        // unguarded promotion
+        ("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", 800),
+        // guarded promotion loses the pawn
+        ("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", -100),
+        // capturing promotion
+        ("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7a8q", 1300),
+        // the king recaptures an undefended queen
+        ("3rk3/8/8/8/8/8/3Q4/3K4 w - - 0 1", "d2d8", -400),
+        // but not a defended one
+        ("3rk3/8/8/8/8/8/3Q4/3RK3 w - - 0 1", "d2d8", 500),
+    ];
+
+    #[test]
+    fn suite() {
+        for (fen, mv, expected) in SUITE {
+            let board = Board::from_str(fen).unwrap();
+            let m = ChessMove::from_str(mv).unwrap();
+            assert!(board.legal(m), "{mv} isn't legal in {fen}");
+            assert_eq!(expected, see(&board, m), "see of {mv} in {fen}");
+            assert!(see_ge(&board, m, expected));
+            assert!(!see_ge(&board, m, expected + 1));
+        }
+    }
+}
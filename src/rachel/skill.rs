@@ -0,0 +1,159 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::rachel::eval::piece_value;
+use crate::rachel::search::SearchInfo;
+use crate::uci::options::Options;
+
+const OPT_SKILL_LEVEL: &str = "Skill Level";
+
+/// Full strength; any lower level weakens play.
+pub const MAX_SKILL_LEVEL: i64 = 20;
+/// Lines searched to choose among, when weakened.
+pub const CANDIDATES: usize = 4;
+
+/// Makes Rachel beatable: a lower level searches less deeply, and chooses
+/// among the best few root moves with noise, sometimes deliberately picking a
+/// bad one.
+///
+/// There's no `UCI_LimitStrength` or `UCI_Elo`: an Elo is only worth
+/// advertising once birch matches have measured what each level plays at,
+/// and they haven't yet.
+pub struct Skill {
+    level: i64,
+    rng: StdRng,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Skill {
+            level: MAX_SKILL_LEVEL,
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl Skill {
+    /// Declare the strength option in `options`, at its default, setting the
+    /// skill `skill` finds in `T`.
+    pub fn declare<T: 'static>(options: Options<T>, skill: fn(&mut T) -> &mut Skill) -> Options<T> {
+        const DECLARED: &str = "Skill option was just declared";
+        options
+            .spin(OPT_SKILL_LEVEL, MAX_SKILL_LEVEL, 0, MAX_SKILL_LEVEL)
+            .on_change(OPT_SKILL_LEVEL, move |t, value| {
+                skill(t).level = value.as_spin().expect(DECLARED);
+                Ok(())
+            })
+            .expect(DECLARED)
+    }
+
+    /// Reseed, for repeatable choices.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// The level in effect, from `Skill Level`.
+    pub fn level(&self) -> i64 {
+        self.level
+    }
+
+    pub fn is_weakened(&self) -> bool {
+        self.level() < MAX_SKILL_LEVEL
+    }
+
+    /// The deepest a weakened search may go.
+    pub fn depth_cap(&self) -> Option<u8> {
+        self.is_weakened().then(|| 1 + self.level() as u8 / 3)
+    }
+
+    /// Choose from `lines` (best first), with more noise the lower the level.
+    /// Each line's score is pushed up at random, by more the worse it is, and
+    /// the highest after pushing is chosen. Occasionally, the worst is chosen
+    /// outright.
+    pub fn pick<'a>(&mut self, lines: &'a [SearchInfo]) -> Option<&'a SearchInfo> {
+        let best = lines.first()?;
+        if !self.is_weakened() || lines.len() == 1 {
+            return Some(best);
+        }
+        let level = self.level();
+        // up to one blunder in seven moves at level zero
+        if self.rng.gen_ratio((MAX_SKILL_LEVEL - level) as u32, 140) {
+            return lines.last();
+        }
+        let weakness = 120 - 2 * level as i32;
+        let worst = lines.last().unwrap().score;
+        let spread = (best.score - worst).min(piece_value(chess::Piece::Pawn));
+        lines.iter().max_by_key(|line| {
+            let push = (weakness * (best.score - line.score)
+                + spread * self.rng.gen_range(0..weakness))
+                / 128;
+            line.score + push
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use chess::ChessMove;
+
+    use super::*;
+
+    fn line(m: &str, score: i32) -> SearchInfo {
+        SearchInfo {
+            depth: 3,
+            multipv: 1,
+            score,
+            nodes: 0,
+            tb_hits: 0,
+            time: Duration::ZERO,
+            pv: vec![ChessMove::from_str(m).unwrap()],
+        }
+    }
+
+    fn options() -> Options<Skill> {
+        Skill::declare(Options::new(), |s| s)
+    }
+
+    /// Set option `name` in `options`, for `skill`.
+    fn set(skill: &mut Skill, options: &mut Options<Skill>, name: &str, value: &str) {
+        assert_eq!(Ok(true), options.set(skill, name, Some(value)));
+    }
+
+    #[test]
+    fn levels() {
+        let mut skill = Skill::default();
+        let mut options = options();
+        assert!(!skill.is_weakened());
+        assert_eq!(None, skill.depth_cap());
+        set(&mut skill, &mut options, "skill level", "5");
+        assert_eq!(Some(2), skill.depth_cap());
+        assert!(options.set(&mut skill, "Skill Level", Some("21")).is_err());
+        assert_eq!(5, skill.level());
+        // no Elo until it's calibrated
+        assert_eq!(Ok(false), options.set(&mut skill, "UCI_Elo", Some("1300")));
+    }
+
+    #[test]
+    fn noise() {
+        let lines = [
+            line("e2e4", 30),
+            line("d2d4", 25),
+            line("g1f3", 10),
+            line("a2a4", -40),
+        ];
+        let mut skill = Skill::default();
+        skill.seed(0);
+        let picks = |skill: &mut Skill| {
+            (0..200)
+                .filter(|_| skill.pick(&lines).unwrap().score != 30)
+                .count()
+        };
+        assert_eq!(0, picks(&mut skill));
+        set(&mut skill, &mut options(), "Skill Level", "0");
+        let weak = picks(&mut skill);
+        assert!(weak > 50, "only {weak} of 200 weren't best");
+    }
+}
@@ -0,0 +1,258 @@
+use std::time::{Duration, Instant};
+
+use chess::{ChessMove, Color};
+use vampirc_uci::{UciSearchControl, UciTimeControl};
+
+/// Time held back from every allocation, for the GUI's (and our) overhead.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+/// Moves assumed left in the game, when the GUI doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// How many times the soft limit the hard limit may stretch to.
+const HARD_LIMIT_FACTOR: u32 = 4;
+/// Each iteration is assumed to take this many times as long as the last.
+const BRANCHING_FACTOR: u32 = 2;
+/// Depth to search when nothing else limits the search at all.
+pub const DEFAULT_DEPTH: u8 = 5;
+
+/// Everything a UCI `go` can ask of the search.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Stop once a mate in this many moves (or fewer) is found.
+    pub mate: Option<u8>,
+    pub movetime: Option<Duration>,
+    pub infinite: bool,
+    /// Searching the opponent's expected reply on their time: don't stop, and
+    /// don't start the clock, until `ponderhit`.
+    pub ponder: bool,
+    /// Only these root moves are to be considered, if any are given.
+    pub search_moves: Vec<ChessMove>,
+    /// The side to move's remaining clock time.
+    pub time_left: Option<Duration>,
+    pub increment: Duration,
+    pub moves_to_go: Option<u8>,
+}
+
+impl Limits {
+    pub fn depth(depth: u8) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        }
+    }
+
+    pub fn from_uci(
+        time_control: Option<&UciTimeControl>,
+        search_control: Option<&UciSearchControl>,
+        side_to_move: Color,
+    ) -> Limits {
+        let mut limits = Limits::default();
+        match time_control {
+            Some(UciTimeControl::Infinite) => limits.infinite = true,
+            Some(UciTimeControl::MoveTime(d)) => limits.movetime = Some(*d),
+            Some(UciTimeControl::TimeLeft {
+                white_time,
+                black_time,
+                white_increment,
+                black_increment,
+                moves_to_go,
+            }) => {
+                let (time, inc) = match side_to_move {
+                    Color::White => (white_time, white_increment),
+                    Color::Black => (black_time, black_increment),
+                };
+                limits.time_left = *time;
+                limits.increment = inc.unwrap_or(Duration::ZERO);
+                limits.moves_to_go = *moves_to_go;
+            }
+            Some(UciTimeControl::Ponder) => limits.ponder = true,
+            None => {}
+        }
+        if let Some(sc) = search_control {
+            limits.depth = sc.depth;
+            limits.nodes = sc.nodes;
+            limits.mate = sc.mate;
+            limits.search_moves = sc.search_moves.clone();
+        }
+        limits
+    }
+
+    /// Whether the search would never stop on its own. A pondering one stops
+    /// at `stop` or `ponderhit`, so isn't, until the latter.
+    pub fn is_unbounded(&self) -> bool {
+        !self.infinite
+            && !self.ponder
+            && self.depth.is_none()
+            && self.nodes.is_none()
+            && self.mate.is_none()
+            && self.movetime.is_none()
+            && self.time_left.is_none()
+    }
+
+    /// Limit an unbounded search to [DEFAULT_DEPTH].
+    pub fn bound(&mut self) {
+        if self.is_unbounded() {
+            self.depth = Some(DEFAULT_DEPTH);
+        }
+    }
+}
+
+/// Decides how long a search may take. The soft limit is consulted between
+/// iterations of iterative deepening, and the hard limit aborts the search
+/// wherever it is.
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &Limits) -> TimeManager {
+        let (soft, hard) = if limits.infinite || limits.ponder {
+            (None, None)
+        } else if let Some(movetime) = limits.movetime {
+            let t = movetime
+                .saturating_sub(MOVE_OVERHEAD)
+                .max(Duration::from_millis(1));
+            (Some(t), Some(t))
+        } else if let Some(left) = limits.time_left {
+            let usable = left.saturating_sub(MOVE_OVERHEAD);
+            let moves = limits
+                .moves_to_go
+                .map_or(DEFAULT_MOVES_TO_GO, |n| n.max(1) as u32);
+            let soft = (usable / moves + limits.increment * 3 / 4).min(usable);
+            // never plan to spend more than half of what's left on one move
+            let hard = (soft * HARD_LIMIT_FACTOR).min(usable / 2).max(soft);
+            (Some(soft), Some(hard))
+        } else {
+            (None, None)
+        };
+        TimeManager {
+            start: Instant::now(),
+            soft,
+            hard,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+
+    /// The same clock, with `1 / n` of the time, for part of a search.
+    pub fn share(&self, n: u32) -> TimeManager {
+        TimeManager {
+            start: self.start,
+            soft: self.soft.map(|s| s / n),
+            hard: self.hard.map(|h| h / n),
+        }
+    }
+
+    /// Whether the search must stop immediately.
+    pub fn hard_expired(&self) -> bool {
+        self.hard.is_some_and(|h| self.elapsed() >= h)
+    }
+
+    /// Whether another iteration, expected to take a multiple of the one which
+    /// just took `last_iteration`, will finish inside the soft limit.
+    pub fn next_iteration_fits(&self, last_iteration: Duration) -> bool {
+        match self.soft {
+            Some(s) => self.elapsed() + last_iteration * BRANCHING_FACTOR < s,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clock(millis: u64, inc: u64, moves_to_go: Option<u8>) -> Limits {
+        Limits {
+            time_left: Some(Duration::from_millis(millis)),
+            increment: Duration::from_millis(inc),
+            moves_to_go,
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn movetime_is_exact() {
+        let tm = TimeManager::new(&Limits {
+            movetime: Some(Duration::from_millis(1000)),
+            ..Limits::default()
+        });
+        assert_eq!(Some(Duration::from_millis(970)), tm.soft_limit());
+        assert_eq!(tm.soft_limit(), tm.hard_limit());
+    }
+
+    #[test]
+    fn sudden_death() {
+        let tm = TimeManager::new(&clock(60_030, 0, None));
+        assert_eq!(Some(Duration::from_millis(2000)), tm.soft_limit());
+        assert_eq!(Some(Duration::from_millis(8000)), tm.hard_limit());
+        let quarter = tm.share(4);
+        assert_eq!(Some(Duration::from_millis(500)), quarter.soft_limit());
+        assert_eq!(Some(Duration::from_millis(2000)), quarter.hard_limit());
+    }
+
+    #[test]
+    fn increment_and_moves_to_go() {
+        let tm = TimeManager::new(&clock(10_030, 1000, Some(10)));
+        assert_eq!(Some(Duration::from_millis(1750)), tm.soft_limit());
+        assert_eq!(Some(Duration::from_millis(5000)), tm.hard_limit());
+    }
+
+    #[test]
+    fn low_on_time() {
+        let tm = TimeManager::new(&clock(130, 1000, None));
+        assert_eq!(Some(Duration::from_millis(100)), tm.soft_limit());
+        assert_eq!(Some(Duration::from_millis(100)), tm.hard_limit());
+    }
+
+    #[test]
+    fn unlimited() {
+        let tm = TimeManager::new(&Limits::depth(5));
+        assert_eq!(None, tm.soft_limit());
+        assert!(!tm.hard_expired());
+        assert!(tm.next_iteration_fits(Duration::from_secs(3600)));
+        assert!(Limits::default().is_unbounded());
+        assert!(!Limits::depth(5).is_unbounded());
+    }
+
+    #[test]
+    fn no_clock_while_pondering() {
+        let mut limits = clock(60_030, 0, None);
+        limits.ponder = true;
+        assert_eq!(None, TimeManager::new(&limits).hard_limit());
+        limits.ponder = false;
+        assert!(TimeManager::new(&limits).hard_limit().is_some());
+    }
+
+    #[test]
+    fn bounded_while_pondering() {
+        let mut limits = Limits {
+            ponder: true,
+            ..Limits::default()
+        };
+        assert!(!limits.is_unbounded());
+        limits.bound();
+        assert_eq!(None, limits.depth);
+        // without a clock, once it's a search of its own
+        limits.ponder = false;
+        limits.bound();
+        assert_eq!(Some(DEFAULT_DEPTH), limits.depth);
+        let mut limits = clock(60_000, 0, None);
+        limits.bound();
+        assert_eq!(None, limits.depth);
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chess::{ChessMove, Piece, ALL_SQUARES};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The score is a lower bound (the search failed high).
+    Lower,
+    /// The score is an upper bound (the search failed low).
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub hash: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<ChessMove>,
+}
+
+const PROMOTIONS: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+impl Entry {
+    /// Everything but the hash, in one word: the move in the low 16 bits, then
+    /// the score, depth, and bound. A zero bound marks an empty slot.
+    fn pack(&self) -> u64 {
+        let m = match self.best_move {
+            Some(m) => {
+                let promotion = m
+                    .get_promotion()
+                    .and_then(|p| PROMOTIONS.iter().position(|&q| q == p))
+                    .map_or(0, |i| i as u64 + 1);
+                1 << 15
+                    | promotion << 12
+                    | (m.get_source().to_index() as u64) << 6
+                    | m.get_dest().to_index() as u64
+            }
+            None => 0,
+        };
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        m | (self.score as i16 as u16 as u64) << 16
+            | (self.depth.clamp(0, u8::MAX as i32) as u64) << 32
+            | bound << 40
+    }
+
+    fn unpack(hash: u64, data: u64) -> Option<Entry> {
+        let bound = match (data >> 40) & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let best_move = (data & 1 << 15 != 0).then(|| {
+            let promotion = match (data >> 12) & 7 {
+                0 => None,
+                p => Some(PROMOTIONS[p as usize - 1]),
+            };
+            ChessMove::new(
+                ALL_SQUARES[(data >> 6 & 63) as usize],
+                ALL_SQUARES[(data & 63) as usize],
+                promotion,
+            )
+        });
+        Some(Entry {
+            hash,
+            depth: (data >> 32 & 0xFF) as i32,
+            score: (data >> 16) as u16 as i16 as i32,
+            bound,
+            best_move,
+        })
+    }
+}
+
+/// Fixed-size, always-replace transposition table, keyed on [chess::Board]'s
+/// Zobrist hash, which any number of threads can share without locking.
+///
+/// Each slot is two words: the entry, and its hash XORed with the entry. A
+/// slot torn by two threads storing at once won't match any position's hash,
+/// so it reads as empty, rather than as a corrupt entry.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let count = (size_mb.max(1) << 20) / std::mem::size_of::<[AtomicU64; 2]>();
+        TranspositionTable {
+            slots: (0..count)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.slots.len() as u64) as usize
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let [key, data] = &self.slots[self.index(hash)];
+        let data = data.load(Ordering::Relaxed);
+        if key.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        Entry::unpack(hash, data)
+    }
+
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: i32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<ChessMove>,
+    ) {
+        // keep the old move if this store doesn't have one of its own
+        let best_move = best_move.or_else(|| self.probe(hash).and_then(|e| e.best_move));
+        let data = Entry {
+            hash,
+            depth,
+            score,
+            bound,
+            best_move,
+        }
+        .pack();
+        let [key, slot] = &self.slots[self.index(hash)];
+        key.store(hash ^ data, Ordering::Relaxed);
+        slot.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for [key, data] in &self.slots {
+            key.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let tt = TranspositionTable::new(1);
+        let m = ChessMove::from_str("a7a8n").unwrap();
+        tt.store(0xDEADBEEF, 7, -31_000, Bound::Lower, Some(m));
+        let e = tt.probe(0xDEADBEEF).unwrap();
+        assert_eq!((7, -31_000, Bound::Lower), (e.depth, e.score, e.bound));
+        assert_eq!(Some(m), e.best_move);
+        // a shallower store without a move keeps the old one
+        tt.store(0xDEADBEEF, 2, 15, Bound::Exact, None);
+        let e = tt.probe(0xDEADBEEF).unwrap();
+        assert_eq!((2, 15, Bound::Exact), (e.depth, e.score, e.bound));
+        assert_eq!(Some(m), e.best_move);
+        assert!(tt.probe(0xDEADBEEE).is_none());
+        tt.clear();
+        assert!(tt.probe(0xDEADBEEF).is_none());
+        // an empty slot doesn't match a zero hash
+        assert!(tt.probe(0).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use std::io;
+use std::str::FromStr;
+
+use chess::{Board, CastleRights, ChessMove, Color};
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess};
+use shakmaty_syzygy::Tablebase;
+
+/// A tablebase result, from the side to move's perspective. The cursed and
+/// blessed results are wins and losses which the fifty-move rule turns into
+/// draws.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl From<shakmaty_syzygy::Wdl> for Wdl {
+    fn from(wdl: shakmaty_syzygy::Wdl) -> Self {
+        match wdl {
+            shakmaty_syzygy::Wdl::Loss => Wdl::Loss,
+            shakmaty_syzygy::Wdl::BlessedLoss => Wdl::BlessedLoss,
+            shakmaty_syzygy::Wdl::Draw => Wdl::Draw,
+            shakmaty_syzygy::Wdl::CursedWin => Wdl::CursedWin,
+            shakmaty_syzygy::Wdl::Win => Wdl::Win,
+        }
+    }
+}
+
+/// Syzygy WDL and DTZ tables, read from local files as they're needed.
+///
+/// [Board] doesn't track the halfmove clock, so every probe assumes the move
+/// into the position reset it. Wins and losses may therefore be reported
+/// which the fifty-move rule would actually make draws.
+pub struct Tablebases {
+    tables: Tablebase<Chess>,
+}
+
+impl Tablebases {
+    /// Open the tables in `path`, one or more directories separated the way
+    /// UCI's `SyzygyPath` has them: by `;` on Windows, and `:` elsewhere.
+    pub fn open(path: &str) -> io::Result<Tablebases> {
+        let separator = if cfg!(windows) { ';' } else { ':' };
+        let mut tables = Tablebase::new();
+        for dir in path.split(separator).filter(|d| !d.trim().is_empty()) {
+            tables.add_directory(dir.trim())?;
+        }
+        if tables.max_pieces() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No Syzygy tables in '{path}'"),
+            ));
+        }
+        Ok(Tablebases { tables })
+    }
+
+    /// Pieces (kings included) in the biggest positions the tables cover.
+    pub fn max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    }
+
+    /// Whether `board` is small enough to be in the tables. Syzygy doesn't
+    /// cover positions where castling is still possible.
+    pub fn covers(&self, board: &Board) -> bool {
+        board.combined().popcnt() as usize <= self.max_pieces()
+            && board.castle_rights(Color::White) == CastleRights::NoRights
+            && board.castle_rights(Color::Black) == CastleRights::NoRights
+    }
+
+    /// The result of `board` with best play, if it's in the tables.
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if !self.covers(board) {
+            return None;
+        }
+        let pos = to_position(board)?;
+        self.tables
+            .probe_wdl_after_zeroing(&pos)
+            .ok()
+            .map(Wdl::from)
+    }
+
+    /// The move which best preserves `board`'s result (winning as quickly as
+    /// possible, or losing as slowly), with that result, if it's in the tables.
+    pub fn best_move(&self, board: &Board) -> Option<(ChessMove, Wdl)> {
+        let wdl = self.probe_wdl(board)?;
+        let pos = to_position(board)?;
+        let (m, _) = self.tables.best_move(&pos).ok()??;
+        let m = ChessMove::from_str(&m.to_uci(CastlingMode::Standard).to_string()).ok()?;
+        board.legal(m).then_some((m, wdl))
+    }
+}
+
+/// Translate `board` to shakmaty's representation, by way of FEN.
+fn to_position(board: &Board) -> Option<Chess> {
+    Fen::from_str(&board.to_string())
+        .ok()?
+        .into_position(CastlingMode::Standard)
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Tables to test against, from the directory in the `SYZYGY_PATH`
+    /// environment variable, as they're too big to keep in the repository.
+    fn tables() -> Tablebases {
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH should be set");
+        Tablebases::open(&path).expect("SYZYGY_PATH should have tables")
+    }
+
+    #[test]
+    fn no_tables() {
+        assert!(Tablebases::open("/no/such/syzygy").is_err());
+    }
+
+    #[test]
+    fn conversion() {
+        let board = Board::from_str("8/8/8/3k4/8/8/4P3/4K3 b - - 0 1").unwrap();
+        assert!(to_position(&board).is_some());
+    }
+
+    #[test]
+    #[ignore = "needs Syzygy tables (KQvK and KRvK at least) in SYZYGY_PATH"]
+    fn probes() {
+        let tables = tables();
+        let kqk = Board::from_str("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1").unwrap();
+        assert_eq!(Some(Wdl::Win), tables.probe_wdl(&kqk));
+        let (m, wdl) = tables.best_move(&kqk).unwrap();
+        assert_eq!(Wdl::Win, wdl);
+        assert!(kqk.legal(m));
+        let kqk = Board::from_str("8/8/8/3k4/8/8/8/Q3K3 b - - 0 1").unwrap();
+        assert_eq!(Some(Wdl::Loss), tables.probe_wdl(&kqk));
+        let castling = Board::from_str("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(!tables.covers(&castling));
+    }
+}
@@ -1,14 +1,24 @@
-use std::thread;
+use std::panic;
 
 use async_std::future::ready;
 use async_std::io;
 use async_std::prelude::*;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures::future::abortable;
+use futures::future::{select, Either};
 use futures::{
-    join, AsyncRead, AsyncWriteExt, FutureExt, Sink, SinkExt, Stream, StreamExt, TryStreamExt,
+    stream, AsyncRead, AsyncWriteExt, FutureExt, Sink, SinkExt, Stream, StreamExt, TryStreamExt,
 };
-use vampirc_uci::{parse_with_unknown, ByteVecUciMessage, UciMessage};
+use pest::error::{Error, ErrorVariant};
+use pest::Position;
+use vampirc_uci::{parse_strict, ByteVecUciMessage, UciMessage, UciSearchControl, UciTimeControl};
+
+pub mod check;
+pub mod client;
+pub mod duplex;
+pub mod engine;
+pub mod options;
+pub mod record;
+pub mod tcp;
 
 /*
 This module started as a vendoring of vampirc-oi, which seems to be abandonware,
@@ -23,19 +33,95 @@ pub type UciReceiver = UnboundedReceiver<UciMessage>;
 pub type UciTrySender = UnboundedSender<io::Result<UciMessage>>;
 pub type UciTryReceiver = UnboundedReceiver<io::Result<UciMessage>>;
 
-pub fn from_reader<'a, R>(reader: io::BufReader<R>) -> Box<UciStream>
+/// Parse one line of input into the messages on it: none if it's blank, and
+/// otherwise at least one, with anything unparseable as [UciMessage::Unknown],
+/// along with why. Never panics, whatever the line.
+pub fn parse_line(line: &str) -> Vec<UciMessage> {
+    // The generated parser will panic when passed "go " due to the trailing
+    // space, despite UciMessage's serialization putting one there. Weird
+    // asymmetry, but trimming mitigates the symptom.
+    let line = line.trim();
+    if line.is_empty() {
+        return Vec::new();
+    }
+    if is_go_ponder_with_more(line) {
+        return vec![UciMessage::Unknown(line.to_string(), None)];
+    }
+    match parse_catching(line) {
+        Ok(msgs) if !msgs.is_empty() => msgs,
+        Ok(_) => vec![UciMessage::Unknown(line.to_string(), None)],
+        Err(panicked) => vec![parse_error(line, panicked)],
+    }
+}
+
+/// An unparseable `line`, for `message`.
+fn parse_error(line: &str, message: String) -> UciMessage {
+    let pos = Position::from_start(line);
+    let error = Error::new_from_pos(ErrorVariant::CustomError { message }, pos);
+    UciMessage::Unknown(line.to_string(), Some(error))
+}
+
+/// Parse `line` with vampirc, catching the panic it has in store for some
+/// lines: it unwraps its number conversions, so e.g. `go depth 300` panics,
+/// rather than failing to parse. Returns what the panic said, if it does.
+///
+/// Heading those lines off beforehand would mean copying vampirc's grammar
+/// and number types, which would drift from it unnoticed, so the panic is
+/// caught instead. The panic hook is left alone, as it's the embedding
+/// binary's, so the panic is still reported on stderr as usual.
+fn parse_catching(line: &str) -> Result<Vec<UciMessage>, String> {
+    let result = panic::catch_unwind(|| match parse_strict(line) {
+        Ok(msgs) => msgs,
+        Err(e) => vec![UciMessage::Unknown(line.to_string(), Some(e))],
+    });
+    result.map_err(|payload| {
+        let what = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "unknown cause".to_string(),
+            },
+        };
+        format!("Parser panicked: {what}")
+    })
+}
+
+/// Whether `line` is a `go ponder` with more after it, like a clock, which
+/// vampirc's [UciMessage::Go] has no room for, having one time control.
+fn is_go_ponder_with_more(line: &str) -> bool {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    tokens.len() > 2 && tokens[0] == "go" && tokens.contains(&"ponder")
+}
+
+/// The rest of a `go ponder` which [parse_line] left unparsed, as a `go` of
+/// its own: the clock to start on `ponderhit`, and the search's limits.
+pub fn parse_go_ponder(text: &str) -> Option<(Option<UciTimeControl>, Option<UciSearchControl>)> {
+    if !is_go_ponder_with_more(text) {
+        return None;
+    }
+    let rest: Vec<&str> = text.split_whitespace().filter(|t| *t != "ponder").collect();
+    match parse_line(&rest.join(" ")).as_slice() {
+        [UciMessage::Go {
+            time_control,
+            search_control,
+        }] => Some((time_control.clone(), search_control.clone())),
+        _ => None,
+    }
+}
+
+/// Messages from `reader`, a line at a time. Blank lines are skipped, and
+/// bytes which aren't UTF-8 are replaced, rather than ending the stream.
+pub fn from_reader<R>(reader: io::BufReader<R>) -> Box<UciStream>
 where
     R: AsyncRead + Unpin + Sync + Send + 'static,
 {
     let stream = reader
-        .lines()
-        .map_ok(|line| {
-            // The generated parser will panic when passed "go " due to the
-            // trailing space, despite UciMessage's serialization putting one
-            // there. Weird asymmetry, but trimming mitigates the symptom.
-            parse_with_unknown(line.trim())
+        .split(b'\n')
+        .map_ok(|bytes| {
+            let msgs = parse_line(&String::from_utf8_lossy(&bytes));
+            stream::iter(msgs.into_iter().map(Ok))
         })
-        .map_ok(|msg_list| msg_list[0].clone());
+        .try_flatten();
 
     Box::new(stream)
 }
@@ -52,66 +138,111 @@ pub fn stdout_msg_sink() -> Box<UciSink> {
     Box::new(sink)
 }
 
+/// Why [run_loops] ended.
+#[derive(Debug)]
+pub enum LoopEnd {
+    /// The inbound stream said `quit`.
+    Quit,
+    /// The inbound stream ended.
+    Eof,
+    /// Reading the inbound stream failed.
+    ReadError(io::Error),
+    /// Writing to the outbound sink failed.
+    WriteError(io::Error),
+    /// Whatever was handling messages went away, dropping the inbound
+    /// receiver, or every outbound sender.
+    ConsumerDropped,
+}
+
+/// Pass messages from `inbound_source` to `inbound_consumer`, and from
+/// `outbound_source` to `outbound_consumer`, until one direction ends. Then
+/// the other's shut down too: the inbound consumer is dropped, so its
+/// receiver ends, and the sink is closed. Outbound messages already queued
+/// are sent first; at EOF, so is everything until every sender's dropped, as
+/// the input ending doesn't mean no one's listening (e.g., a piped script).
 pub async fn run_loops(
     mut inbound_source: Box<UciStream>,
-    mut inbound_consumer: UciTrySender,
+    inbound_consumer: UciTrySender,
     mut outbound_source: UciReceiver,
     mut outbound_consumer: Box<UciSink>,
-) {
-    let outb = async {
-        while let Some(msg) = StreamExt::next(&mut outbound_source).await {
-            let sr = outbound_consumer.send(msg).await;
-            if let Err(err) = sr {
-                eprintln!(
-                    "[{:?}] Error while sending message through the outbound channel: {}",
-                    thread::current().id(),
-                    err
-                );
-            }
+) -> LoopEnd {
+    let inb = Box::pin(forward_inbound(&mut *inbound_source, inbound_consumer));
+    let outb = Box::pin(forward_outbound(
+        &mut outbound_source,
+        &mut *outbound_consumer,
+    ));
+    let end = match select(inb, outb).await {
+        Either::Left((LoopEnd::Eof, outb)) => match outb.await {
+            Ok(()) => LoopEnd::Eof,
+            Err(e) => return LoopEnd::WriteError(e),
+        },
+        Either::Left((end, outb)) => {
+            drop(outb);
+            end
+        }
+        Either::Right((Ok(()), inb)) => {
+            drop(inb);
+            LoopEnd::ConsumerDropped
+        }
+        Either::Right((Err(e), inb)) => {
+            drop(inb);
+            return LoopEnd::WriteError(e);
         }
     };
+    let flushed = async {
+        while let Ok(Some(msg)) = outbound_source.try_next() {
+            outbound_consumer.send(msg).await?;
+        }
+        outbound_consumer.close().await
+    };
+    match flushed.await {
+        Ok(()) => end,
+        Err(e) => LoopEnd::WriteError(e),
+    }
+}
 
-    let (aoutb, aoutbh) = abortable(outb);
-
-    let inb = async {
-        loop {
-            let msg_result = inbound_source.try_next().await;
-            if let Ok(msg_opt) = msg_result {
-                if msg_opt.is_none() {
-                    break;
-                } else {
-                    let msg = msg_opt.unwrap();
-                    let quit = match msg {
-                        UciMessage::Quit => true,
-                        _ => false,
-                    };
-                    inbound_consumer.send(Ok(msg)).await.unwrap();
-                    if quit {
-                        // if we got a Quit, disconnect
-                        aoutbh.abort();
-                        break;
-                    }
-                }
-            } else {
-                inbound_consumer
-                    .send(Err(msg_result.err().unwrap()))
-                    .await
-                    .unwrap();
+/// Returns why it stopped, having dropped `consumer`.
+async fn forward_inbound(source: &mut UciStream, mut consumer: UciTrySender) -> LoopEnd {
+    loop {
+        let msg = match StreamExt::next(source).await {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => {
+                // best effort, as it's the end either way
+                let copy = io::Error::new(e.kind(), e.to_string());
+                let _ = consumer.send(Err(copy)).await;
+                return LoopEnd::ReadError(e);
             }
+            None => return LoopEnd::Eof,
+        };
+        let quit = msg == UciMessage::Quit;
+        if consumer.send(Ok(msg)).await.is_err() {
+            return LoopEnd::ConsumerDropped;
         }
-    };
+        if quit {
+            return LoopEnd::Quit;
+        }
+    }
+}
 
-    let (_, _) = join!(inb, aoutb);
+/// Returns once `source` ends, or writing to `sink` fails.
+async fn forward_outbound(source: &mut UciReceiver, sink: &mut UciSink) -> io::Result<()> {
+    while let Some(msg) = StreamExt::next(source).await {
+        sink.send(msg).await?;
+    }
+    Ok(())
 }
 
-pub async fn run_std_loops(inbound_consumer: UciTrySender, outbound_source: UciReceiver) {
+pub async fn run_std_loops(
+    inbound_consumer: UciTrySender,
+    outbound_source: UciReceiver,
+) -> LoopEnd {
     run_loops(
         stdin_msg_stream(),
         inbound_consumer,
         outbound_source,
         stdout_msg_sink(),
     )
-    .await;
+    .await
 }
 
 pub fn new_channel() -> (UciSender, UciReceiver) {
@@ -121,3 +252,163 @@ pub fn new_channel() -> (UciSender, UciReceiver) {
 pub fn new_try_channel() -> (UciTrySender, UciTryReceiver) {
     unbounded::<io::Result<UciMessage>>()
 }
+
+#[cfg(test)]
+mod test {
+    use async_std::task::block_on;
+    use futures::future::join;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn read_all(input: &[u8]) -> Vec<UciMessage> {
+        let reader = io::BufReader::new(io::Cursor::new(input.to_vec()));
+        block_on(from_reader(reader).try_collect()).unwrap()
+    }
+
+    #[test]
+    fn lines() {
+        let msgs = read_all(b"uci\n\n   \r\ngo \r\nisready\nnonsense here\n\xFF\xFEquit");
+        assert_eq!(5, msgs.len(), "{msgs:?}");
+        assert_eq!(UciMessage::Uci, msgs[0]);
+        assert!(matches!(msgs[1], UciMessage::Go { .. }));
+        assert_eq!(UciMessage::IsReady, msgs[2]);
+        assert!(matches!(&msgs[3], UciMessage::Unknown(text, _) if text == "nonsense here"));
+        assert!(matches!(msgs[4], UciMessage::Unknown(..)));
+    }
+
+    #[test]
+    fn go_ponder_with_clock() {
+        let line = "go ponder wtime 60000 btime 50000 depth 12";
+        assert!(matches!(&parse_line(line)[..], [UciMessage::Unknown(text, None)] if text == line));
+        let (time_control, search_control) = parse_go_ponder(line).unwrap();
+        assert!(matches!(
+            time_control,
+            Some(UciTimeControl::TimeLeft { white_time: Some(w), .. }) if w.as_millis() == 60000
+        ));
+        assert_eq!(Some(12), search_control.and_then(|sc| sc.depth));
+        // on its own, vampirc has it
+        assert!(matches!(
+            &parse_line("go ponder")[..],
+            [UciMessage::Go {
+                time_control: Some(UciTimeControl::Ponder),
+                ..
+            }]
+        ));
+        assert_eq!(None, parse_go_ponder("go ponder"));
+        assert_eq!(None, parse_go_ponder("go wtime 1000 btime 1000"));
+    }
+
+    #[test]
+    fn errors() {
+        let lines = [
+            "nonsense here",
+            "go depth 300",
+            "info nodes -1",
+            "go wtime 1e9",
+        ];
+        for line in lines {
+            let msgs = parse_line(line);
+            assert!(matches!(&msgs[..], [UciMessage::Unknown(text, Some(_))] if text == line));
+        }
+        let msgs = parse_line("go depth 255");
+        assert!(matches!(&msgs[..], [UciMessage::Go { .. }]), "{msgs:?}");
+        // numbers in a string are just words
+        let msgs = parse_line("info string depth 300");
+        assert!(matches!(&msgs[..], [UciMessage::Info(_)]), "{msgs:?}");
+    }
+
+    #[test]
+    fn blank() {
+        assert!(parse_line("").is_empty());
+        assert!(parse_line(" \t\r").is_empty());
+        assert!(read_all(b"\n\n\r\n").is_empty());
+    }
+
+    /// Lines starting like messages, but most likely malformed.
+    const UCI_LIKE: &str =
+        "(go|position|setoption|info|id|option|bestmove|register)( [a-z0-9=/ ]{0,40})?";
+
+    fn input(text: &'static str) -> Box<UciStream> {
+        from_reader(io::BufReader::new(io::Cursor::new(text)))
+    }
+
+    /// A sink which keeps what it's sent, for the receiver.
+    fn keeping_sink() -> (Box<UciSink>, UciReceiver) {
+        let (tx, rx) = new_channel();
+        let sink = tx.sink_map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e));
+        (Box::new(sink), rx)
+    }
+
+    #[test]
+    fn ends_on_quit() {
+        let (itx, irx) = new_try_channel();
+        let (otx, orx) = new_channel();
+        let (sink, written) = keeping_sink();
+        otx.unbounded_send(UciMessage::ReadyOk).unwrap();
+        let end = block_on(run_loops(input("uci\nquit\nisready\n"), itx, orx, sink));
+        assert!(matches!(end, LoopEnd::Quit), "{end:?}");
+        // quit is passed along, but nothing after it
+        let read: Vec<_> = block_on(irx.try_collect()).unwrap();
+        assert_eq!(vec![UciMessage::Uci, UciMessage::Quit], read);
+        // what was already queued is still written
+        let written: Vec<_> = block_on(StreamExt::collect(written));
+        assert_eq!(vec![UciMessage::ReadyOk], written);
+    }
+
+    #[test]
+    fn ends_on_eof() {
+        let (itx, irx) = new_try_channel();
+        let (otx, orx) = new_channel();
+        let (sink, written) = keeping_sink();
+        // what's sent after the input ends is still written
+        let reply = async move {
+            let read: Vec<_> = irx.try_collect().await.unwrap();
+            assert_eq!(vec![UciMessage::Uci], read);
+            otx.unbounded_send(UciMessage::UciOk).unwrap();
+        };
+        let (end, ()) = block_on(join(run_loops(input("uci\n"), itx, orx, sink), reply));
+        assert!(matches!(end, LoopEnd::Eof), "{end:?}");
+        let written: Vec<_> = block_on(StreamExt::collect(written));
+        assert_eq!(vec![UciMessage::UciOk], written);
+    }
+
+    #[test]
+    fn ends_without_consumer() {
+        let (itx, irx) = new_try_channel();
+        drop(irx);
+        let (_otx, orx) = new_channel();
+        let (sink, _written) = keeping_sink();
+        let end = block_on(run_loops(input("uci\n"), itx, orx, sink));
+        assert!(matches!(end, LoopEnd::ConsumerDropped), "{end:?}");
+    }
+
+    #[test]
+    fn ends_on_write_error() {
+        let (itx, _irx) = new_try_channel();
+        let (otx, orx) = new_channel();
+        let (sink, written) = keeping_sink();
+        drop(written);
+        otx.unbounded_send(UciMessage::ReadyOk).unwrap();
+        // never ends of its own accord
+        let inbound = Box::new(stream::pending());
+        let end = block_on(run_loops(inbound, itx, orx, sink));
+        assert!(matches!(end, LoopEnd::WriteError(_)), "{end:?}");
+    }
+
+    proptest! {
+        #[test]
+        fn any_bytes(input in proptest::collection::vec(any::<u8>(), 0..512)) {
+            let lines = input.split(|&b| b == b'\n');
+            let non_blank = lines
+                .filter(|l| !String::from_utf8_lossy(l).trim().is_empty())
+                .count();
+            prop_assert!(read_all(&input).len() >= non_blank);
+        }
+
+        #[test]
+        fn uci_like(line in UCI_LIKE) {
+            prop_assert!(!parse_line(&line).is_empty());
+        }
+    }
+}
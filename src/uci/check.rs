@@ -0,0 +1,349 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use async_std::io;
+use async_std::task::sleep;
+use chess::{Board, ChessMove};
+use vampirc_uci::{UciFen, UciMessage, UciSearchControl, UciTimeControl};
+
+use crate::uci::client::{is_best_move, BestMove, Client, Handshake};
+use crate::uci::options::name_of;
+
+/// Something a UCI engine ought to do, checked on a fresh instance of it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Check {
+    /// Answers `uci` with its name, author, and `uciok`, then `isready`.
+    Handshake,
+    /// Answers `isready` mid-search, without ending the search.
+    ReadyWhileSearching,
+    /// Sends `bestmove` promptly on `stop`.
+    StopLatency,
+    /// Searches the position after `position ... moves`.
+    PositionMoves,
+    /// Survives commands it can't make sense of.
+    InvalidInput,
+    /// Takes a `setoption` for each option it declares, at its default.
+    SetOptions,
+    /// Hangs up, and exits cleanly, on `quit`.
+    Quit,
+}
+
+pub const ALL_CHECKS: [Check; 7] = [
+    Check::Handshake,
+    Check::ReadyWhileSearching,
+    Check::StopLatency,
+    Check::PositionMoves,
+    Check::InvalidInput,
+    Check::SetOptions,
+    Check::Quit,
+];
+
+/// How long an engine has to answer, and to stop searching.
+#[derive(Clone, Debug)]
+pub struct Timeouts {
+    pub reply: Duration,
+    pub stop: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            reply: Duration::from_secs(10),
+            stop: Duration::from_millis(250),
+        }
+    }
+}
+
+/// How long a search runs before it's poked.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Lines no engine should choke on.
+const INVALID_INPUT: &[&str] = &[
+    "xyzzy",
+    "position startpos moves e2e5",
+    "position fen not/a/fen w - - 0 1",
+    "setoption name No Such Option value 42",
+    "\u{1b}[2J",
+];
+
+/// How a check went.
+pub struct Report {
+    pub check: Check,
+    pub result: Result<(), String>,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.check.name();
+        let elapsed = self.elapsed;
+        match &self.result {
+            Ok(()) => write!(f, "PASS {name} ({elapsed:.1?})"),
+            Err(e) => write!(f, "FAIL {name} ({elapsed:.1?}): {e}"),
+        }
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL_CHECKS
+            .into_iter()
+            .find(|c| c.name() == s)
+            .ok_or_else(|| format!("No check named {s}"))
+    }
+}
+
+fn err(e: io::Error) -> String {
+    e.to_string()
+}
+
+fn position(fen: Option<&str>, moves: &[ChessMove]) -> UciMessage {
+    UciMessage::Position {
+        startpos: fen.is_none(),
+        fen: fen.map(|f| UciFen(f.to_string())),
+        moves: moves.to_vec(),
+    }
+}
+
+fn go_infinite() -> UciMessage {
+    UciMessage::Go {
+        time_control: Some(UciTimeControl::Infinite),
+        search_control: None,
+    }
+}
+
+fn go_depth(depth: u8) -> UciMessage {
+    UciMessage::Go {
+        time_control: None,
+        search_control: Some(UciSearchControl::depth(depth)),
+    }
+}
+
+/// That `reply` is a legal move on `board`.
+fn legal(board: &Board, reply: &BestMove) -> Result<(), String> {
+    match reply.best_move {
+        Some(m) if board.legal(m) => Ok(()),
+        Some(m) => Err(format!("illegal bestmove {m} in {board}")),
+        None => Err(format!("null bestmove in {board}")),
+    }
+}
+
+impl Check {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Check::Handshake => "handshake",
+            Check::ReadyWhileSearching => "isready-searching",
+            Check::StopLatency => "stop",
+            Check::PositionMoves => "position-moves",
+            Check::InvalidInput => "invalid-input",
+            Check::SetOptions => "setoption",
+            Check::Quit => "quit",
+        }
+    }
+
+    /// Shake hands with the engine at the end of `client`, check it, and
+    /// have it quit.
+    pub async fn run(self, mut client: Client, timeouts: &Timeouts) -> Report {
+        let start = Instant::now();
+        let handshake = client.uci(timeouts.reply).await;
+        let result = match handshake {
+            Err(e) => Err(format!("no handshake: {e}")),
+            Ok(handshake) => match self {
+                Check::Quit => quit(client, timeouts).await,
+                _ => {
+                    let result = self.check(&mut client, &handshake, timeouts).await;
+                    let _ = client.send(UciMessage::Quit).await;
+                    let _ = client.close(timeouts.reply).await;
+                    result
+                }
+            },
+        };
+        Report {
+            check: self,
+            result,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    async fn check(
+        self,
+        client: &mut Client,
+        handshake: &Handshake,
+        timeouts: &Timeouts,
+    ) -> Result<(), String> {
+        match self {
+            Check::Handshake => {
+                if handshake.name.is_none() {
+                    return Err("no id name".to_string());
+                }
+                if handshake.author.is_none() {
+                    return Err("no id author".to_string());
+                }
+                client.is_ready(timeouts.reply).await.map_err(err)
+            }
+            Check::ReadyWhileSearching => ready_while_searching(client, timeouts).await,
+            Check::StopLatency => stop_latency(client, timeouts).await,
+            Check::PositionMoves => position_moves(client, timeouts).await,
+            Check::InvalidInput => invalid_input(client, timeouts).await,
+            Check::SetOptions => set_options(client, handshake, timeouts).await,
+            Check::Quit => unreachable!("Quit needs the client to itself"),
+        }
+    }
+}
+
+async fn ready_while_searching(client: &mut Client, timeouts: &Timeouts) -> Result<(), String> {
+    client.send(position(None, &[])).await.map_err(err)?;
+    client.send(go_infinite()).await.map_err(err)?;
+    sleep(SETTLE).await;
+    client.send(UciMessage::IsReady).await.map_err(err)?;
+    let msgs = client
+        .recv_until(timeouts.reply, |m| *m == UciMessage::ReadyOk)
+        .await
+        .map_err(|e| format!("no readyok mid-search: {e}"))?;
+    if msgs.iter().any(is_best_move) {
+        return Err("bestmove to an infinite search, before stop".to_string());
+    }
+    client.send(UciMessage::Stop).await.map_err(err)?;
+    let reply = client.best_move(timeouts.reply).await.map_err(err)?;
+    legal(&Board::default(), &reply)
+}
+
+async fn stop_latency(client: &mut Client, timeouts: &Timeouts) -> Result<(), String> {
+    client.send(position(None, &[])).await.map_err(err)?;
+    client.send(go_infinite()).await.map_err(err)?;
+    sleep(SETTLE).await;
+    let stopped = Instant::now();
+    client.send(UciMessage::Stop).await.map_err(err)?;
+    let reply = client.best_move(timeouts.reply).await.map_err(err)?;
+    let latency = stopped.elapsed();
+    legal(&Board::default(), &reply)?;
+    if latency > timeouts.stop {
+        return Err(format!(
+            "bestmove {latency:.1?} after stop, over {:?}",
+            timeouts.stop
+        ));
+    }
+    Ok(())
+}
+
+async fn position_moves(client: &mut Client, timeouts: &Timeouts) -> Result<(), String> {
+    let games = [
+        (None, &["e2e4", "e7e5", "g1f3"][..]),
+        // black to move, with the king in check
+        (Some("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1"), &["e2e3"][..]),
+        (
+            Some("4k3/8/8/8/8/8/3P4/4K3 w - - 0 1"),
+            &["d2d4", "e8d7", "d4d5"][..],
+        ),
+    ];
+    for (fen, moves) in games {
+        let moves: Vec<ChessMove> = moves
+            .iter()
+            .map(|m| ChessMove::from_str(m).expect("Move should parse"))
+            .collect();
+        let board = fen.map_or_else(Board::default, |f| {
+            Board::from_str(f).expect("FEN should parse")
+        });
+        let board = moves.iter().fold(board, |b, m| b.make_move_new(*m));
+        client.send(position(fen, &moves)).await.map_err(err)?;
+        client.send(go_depth(2)).await.map_err(err)?;
+        let reply = client.best_move(timeouts.reply).await.map_err(err)?;
+        legal(&board, &reply)?;
+    }
+    Ok(())
+}
+
+async fn invalid_input(client: &mut Client, timeouts: &Timeouts) -> Result<(), String> {
+    for line in INVALID_INPUT {
+        let msg = UciMessage::Unknown(line.to_string(), None);
+        client.send(msg).await.map_err(err)?;
+    }
+    client
+        .is_ready(timeouts.reply)
+        .await
+        .map_err(|e| format!("not ready after invalid input: {e}"))?;
+    // and still able to play
+    client.send(position(None, &[])).await.map_err(err)?;
+    client.send(go_depth(1)).await.map_err(err)?;
+    let reply = client.best_move(timeouts.reply).await.map_err(err)?;
+    legal(&Board::default(), &reply)
+}
+
+async fn set_options(
+    client: &mut Client,
+    handshake: &Handshake,
+    timeouts: &Timeouts,
+) -> Result<(), String> {
+    for config in handshake.options.uci_options() {
+        let name = name_of(&config);
+        let value = handshake.options.get(name).and_then(|v| v.to_uci());
+        client
+            .send(UciMessage::SetOption {
+                name: name.to_string(),
+                value: value.clone(),
+            })
+            .await
+            .map_err(err)?;
+        client.is_ready(timeouts.reply).await.map_err(|e| {
+            let value = value.as_deref().unwrap_or("");
+            format!("not ready after setting {name} to '{value}': {e}")
+        })?;
+    }
+    Ok(())
+}
+
+async fn quit(mut client: Client, timeouts: &Timeouts) -> Result<(), String> {
+    client.send(UciMessage::Quit).await.map_err(err)?;
+    let hung_up = match client.recv_until(timeouts.reply, |_| false).await {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+        Err(e) => Err(format!("didn't hang up: {e}")),
+        Ok(_) => unreachable!("Nothing should be done"),
+    };
+    let closed = match client.close(timeouts.reply).await {
+        Ok(Some(status)) if !status.success() => Err(format!("exited with {status}")),
+        Ok(_) => Ok(()),
+        Err(e) => Err(err(e)),
+    };
+    hung_up.and(closed)
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::task::block_on;
+
+    use crate::rachel::engine::Rachel;
+
+    use super::*;
+
+    #[test]
+    fn names() {
+        for check in ALL_CHECKS {
+            assert_eq!(Ok(check), check.name().parse());
+        }
+        assert!("everything".parse::<Check>().is_err());
+    }
+
+    #[test]
+    fn rachel_passes() {
+        let timeouts = Timeouts::default();
+        for check in ALL_CHECKS {
+            let report = block_on(check.run(Client::builtin(Rachel::new()), &timeouts));
+            assert!(report.result.is_ok(), "{report}");
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn cat_fails() {
+        let timeouts = Timeouts {
+            reply: Duration::from_millis(100),
+            ..Timeouts::default()
+        };
+        // echoes `uci`, but never says `uciok`
+        let report = block_on(Check::Handshake.run(Client::spawn("cat").unwrap(), &timeouts));
+        assert!(report.result.unwrap_err().starts_with("no handshake"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::process::{ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use async_std::future::{ready, timeout};
+use async_std::io;
+use async_std::net::TcpStream;
+use async_std::process::{Child, Command};
+use async_std::task::block_on;
+use chess::ChessMove;
+use futures::channel::oneshot;
+use futures::{sink, stream, AsyncWriteExt, SinkExt, StreamExt};
+use vampirc_uci::{ByteVecUciMessage, UciMessage};
+
+use crate::uci::duplex::{duplex, Pipe};
+use crate::uci::engine::{run_engine, Engine};
+use crate::uci::options::Options;
+use crate::uci::record::{Direction, Recorder};
+use crate::uci::tcp::tcp_pipe;
+use crate::uci::{from_reader, UciSink, UciStream};
+
+/// What an engine says of itself in reply to `uci`.
+pub struct Handshake {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub options: Options,
+}
+
+/// An engine's answer to `go`.
+#[derive(Clone, Debug)]
+pub struct BestMove {
+    /// None if it had no legal moves.
+    pub best_move: Option<ChessMove>,
+    pub ponder: Option<ChessMove>,
+    /// The `info` it sent along the way.
+    pub info: Vec<UciMessage>,
+}
+
+/// What's at the other end of a [Client], to wait for once it's done.
+enum Peer {
+    Process(Child),
+    /// The thread, and word that it's done, which can be waited on for a
+    /// while, unlike joining.
+    Thread(JoinHandle<()>, oneshot::Receiver<()>),
+    Remote,
+}
+
+/// The GUI side of UCI: talks to an engine, whether a process, a thread, or
+/// across a network, with a future for each exchange.
+pub struct Client {
+    sink: Box<UciSink>,
+    stream: Box<UciStream>,
+    peer: Peer,
+}
+
+fn timed_out(what: &str, within: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("No {what} within {within:?}"),
+    )
+}
+
+impl Client {
+    /// Run `cmd`, and talk to it through its stdin and stdout.
+    pub fn spawn(cmd: &str) -> io::Result<Client> {
+        let mut child = Command::new(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("Child's stdin should be piped");
+        let stdout = child.stdout.take().expect("Child's stdout should be piped");
+        let sink = stdin
+            .into_sink()
+            .with(|msg: UciMessage| ready(Ok::<_, io::Error>(ByteVecUciMessage::from(msg))));
+        Ok(Client {
+            sink: Box::new(sink),
+            stream: from_reader(io::BufReader::new(stdout)),
+            peer: Peer::Process(child),
+        })
+    }
+
+    /// Run `engine` on a thread, and talk to it through an in-memory pipe.
+    pub fn builtin<E>(mut engine: E) -> Client
+    where
+        E: Engine + Send + 'static,
+    {
+        Client::on_thread(move |pipe| {
+            block_on(run_engine(&mut engine, pipe.stream, pipe.sink));
+        })
+    }
+
+    /// Run `serve` on a thread, and talk to whatever it serves UCI to the
+    /// pipe it's given (e.g., an engine which speaks something else).
+    pub fn on_thread<F>(serve: F) -> Client
+    where
+        F: FnOnce(Pipe) + Send + 'static,
+    {
+        let (ours, theirs) = duplex();
+        let (done_tx, done_rx) = oneshot::channel();
+        let handle = thread::spawn(move || {
+            serve(theirs);
+            let _ = done_tx.send(());
+        });
+        Client {
+            sink: ours.sink,
+            stream: ours.stream,
+            peer: Peer::Thread(handle, done_rx),
+        }
+    }
+
+    /// Talk to an engine served at `addr`.
+    pub async fn connect(addr: &str) -> io::Result<Client> {
+        let pipe = tcp_pipe(TcpStream::connect(addr).await?);
+        Ok(Client {
+            sink: pipe.sink,
+            stream: pipe.stream,
+            peer: Peer::Remote,
+        })
+    }
+
+    pub async fn send(&mut self, msg: UciMessage) -> io::Result<()> {
+        self.sink.send(msg).await
+    }
+
+    /// The engine's next message.
+    pub async fn recv(&mut self) -> io::Result<UciMessage> {
+        match self.stream.next().await {
+            Some(r) => r,
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Engine hung up",
+            )),
+        }
+    }
+
+    /// The engine's messages, through the first which is `done`, if that one
+    /// comes `within` the time allowed.
+    pub async fn recv_until<F>(&mut self, within: Duration, done: F) -> io::Result<Vec<UciMessage>>
+    where
+        F: Fn(&UciMessage) -> bool,
+    {
+        let recv = async {
+            let mut msgs = vec![];
+            loop {
+                let msg = self.recv().await?;
+                let last = done(&msg);
+                msgs.push(msg);
+                if last {
+                    return Ok(msgs);
+                }
+            }
+        };
+        timeout(within, recv)
+            .await
+            .unwrap_or_else(|_| Err(timed_out("reply", within)))
+    }
+
+    /// [Client::recv_until], saying `what` didn't come if it times out.
+    async fn expect<F>(
+        &mut self,
+        what: &str,
+        within: Duration,
+        done: F,
+    ) -> io::Result<Vec<UciMessage>>
+    where
+        F: Fn(&UciMessage) -> bool,
+    {
+        self.recv_until(within, done)
+            .await
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::TimedOut => timed_out(what, within),
+                _ => e,
+            })
+    }
+
+    /// Send `uci`, and collect the engine's `id` and `option`s, up to `uciok`.
+    pub async fn uci(&mut self, within: Duration) -> io::Result<Handshake> {
+        self.send(UciMessage::Uci).await?;
+        let msgs = self
+            .expect("uciok", within, |m| *m == UciMessage::UciOk)
+            .await?;
+        let mut handshake = Handshake {
+            name: None,
+            author: None,
+            options: Options::new(),
+        };
+        for msg in msgs {
+            match msg {
+                UciMessage::Id { name, author } => {
+                    handshake.name = name.or(handshake.name);
+                    handshake.author = author.or(handshake.author);
+                }
+                UciMessage::Option(config) => handshake.options.declare(config),
+                _ => {}
+            }
+        }
+        Ok(handshake)
+    }
+
+    /// Send `isready`, and wait for `readyok`.
+    pub async fn is_ready(&mut self, within: Duration) -> io::Result<()> {
+        self.send(UciMessage::IsReady).await?;
+        self.expect("readyok", within, |m| *m == UciMessage::ReadyOk)
+            .await?;
+        Ok(())
+    }
+
+    /// Wait for `bestmove`, from a search already started.
+    pub async fn best_move(&mut self, within: Duration) -> io::Result<BestMove> {
+        let mut info = self.expect("bestmove", within, is_best_move).await?;
+        let (best_move, ponder) = match info.pop() {
+            Some(UciMessage::BestMove { best_move, ponder }) => (Some(best_move), ponder),
+            // the null move, which vampirc doesn't parse
+            _ => (None, None),
+        };
+        info.retain(|m| matches!(m, UciMessage::Info(_)));
+        Ok(BestMove {
+            best_move,
+            ponder,
+            info,
+        })
+    }
+
+    /// Hand the engine's messages over, to be read elsewhere (e.g., on a
+    /// thread of their own). This client receives nothing after.
+    pub fn take_stream(&mut self) -> Box<UciStream> {
+        std::mem::replace(&mut self.stream, Box::new(stream::empty()))
+    }
+
+    /// Hand the sink to the engine over, to be sent to elsewhere. What this
+    /// client sends after goes nowhere.
+    pub fn take_sink(&mut self) -> Box<UciSink> {
+        let nowhere = sink::drain::<UciMessage>().sink_map_err(|never| match never {});
+        std::mem::replace(&mut self.sink, Box::new(nowhere))
+    }
+
+    /// Record everything sent and received from here on with `recorder`, to
+    /// replay later. Start before `uci`, for a session that can be.
+    pub fn record(&mut self, recorder: &Recorder) {
+        let pipe = Pipe {
+            stream: self.take_stream(),
+            sink: self.take_sink(),
+        };
+        let pipe = recorder.tap(pipe, Direction::ToEngine);
+        self.stream = pipe.stream;
+        self.sink = pipe.sink;
+    }
+
+    /// Hang up, and wait up to `within` for the engine to finish, killing it
+    /// if it's a process which won't, or giving up on it with an error if it's
+    /// a thread. Returns its exit status, if a process. Say `quit` first, to
+    /// be polite.
+    pub async fn close(self, within: Duration) -> io::Result<Option<ExitStatus>> {
+        drop(self.sink);
+        drop(self.stream);
+        match self.peer {
+            Peer::Process(mut child) => match timeout(within, child.status()).await {
+                Ok(status) => status.map(Some),
+                Err(_) => {
+                    child.kill()?;
+                    eprintln!("[CLIENT] ! killed still-running engine");
+                    child.status().await.map(Some)
+                }
+            },
+            Peer::Thread(handle, done) => {
+                // a panic drops the sender, which is done too
+                if timeout(within, done).await.is_err() {
+                    // there's no killing a thread, so it's left to it
+                    return Err(timed_out("exit from the engine's thread", within));
+                }
+                handle
+                    .join()
+                    .map_err(|_| io::Error::other("Engine panicked"))?;
+                Ok(None)
+            }
+            Peer::Remote => Ok(None),
+        }
+    }
+}
+
+/// Whether `msg` is a `bestmove`, including one of the null move, which
+/// vampirc leaves unparsed.
+pub fn is_best_move(msg: &UciMessage) -> bool {
+    match msg {
+        UciMessage::BestMove { .. } => true,
+        UciMessage::Unknown(text, _) => text.starts_with("bestmove"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chess::Board;
+    use vampirc_uci::UciSearchControl;
+
+    use crate::rachel::engine::Rachel;
+
+    use super::*;
+
+    const WITHIN: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn builtin() {
+        block_on(async {
+            let mut client = Client::builtin(Rachel::new());
+            let handshake = client.uci(WITHIN).await.unwrap();
+            assert_eq!(Some("Rachel"), handshake.name.as_deref());
+            assert!(handshake.options.get_spin("MultiPV").is_some());
+            client.is_ready(WITHIN).await.unwrap();
+
+            let e4 = ChessMove::from_str("e2e4").unwrap();
+            let position = UciMessage::Position {
+                startpos: true,
+                fen: None,
+                moves: vec![e4],
+            };
+            client.send(position).await.unwrap();
+            let go = UciMessage::Go {
+                time_control: None,
+                search_control: Some(UciSearchControl::depth(2)),
+            };
+            client.send(go).await.unwrap();
+            let reply = client.best_move(WITHIN).await.unwrap();
+            let board = Board::default().make_move_new(e4);
+            assert!(reply.best_move.is_some_and(|m| board.legal(m)));
+            assert!(!reply.info.is_empty());
+
+            // no search, so no best move
+            let nothing = client.best_move(Duration::from_millis(50)).await;
+            assert_eq!(io::ErrorKind::TimedOut, nothing.unwrap_err().kind());
+
+            client.send(UciMessage::Quit).await.unwrap();
+            assert!(client.close(WITHIN).await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn process() {
+        block_on(async {
+            // not an engine, but it'll echo what it's sent
+            let mut client = Client::spawn("cat").unwrap();
+            client.send(UciMessage::ReadyOk).await.unwrap();
+            assert_eq!(UciMessage::ReadyOk, client.recv().await.unwrap());
+            let status = client.close(WITHIN).await.unwrap();
+            assert!(status.is_some_and(|s| s.success()));
+        });
+    }
+
+    #[test]
+    fn stuck_thread() {
+        // deaf to hanging up, like an engine stuck in a search
+        let client = Client::on_thread(|_pipe| thread::sleep(Duration::from_secs(1)));
+        let closed = block_on(client.close(Duration::from_millis(50)));
+        assert_eq!(io::ErrorKind::TimedOut, closed.unwrap_err().kind());
+    }
+}
@@ -0,0 +1,77 @@
+use async_std::future::ready;
+use async_std::io;
+use futures::channel::mpsc::unbounded;
+use futures::{stream, SinkExt, StreamExt};
+use vampirc_uci::UciMessage;
+
+use crate::uci::{parse_line, UciSink, UciStream};
+
+/// One end of an in-memory connection, as [crate::uci::run_loops] wants it.
+pub struct Pipe {
+    pub stream: Box<UciStream>,
+    pub sink: Box<UciSink>,
+}
+
+/// Two ends of an in-memory connection: what's sent into either one's sink
+/// comes out of the other's stream. Messages cross as text, just as through a
+/// process's stdin and stdout, so an engine and its client can be connected
+/// in one process, with the protocol exercised as it would be between two.
+pub fn duplex() -> (Pipe, Pipe) {
+    let (a, b) = (pipe_half(), pipe_half());
+    (
+        Pipe {
+            stream: a.1,
+            sink: b.0,
+        },
+        Pipe {
+            stream: b.1,
+            sink: a.0,
+        },
+    )
+}
+
+/// A sink, and the stream its messages come out of.
+fn pipe_half() -> (Box<UciSink>, Box<UciStream>) {
+    let (tx, rx) = unbounded::<String>();
+    let sink = tx
+        .sink_map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))
+        .with(|msg: UciMessage| ready(Ok::<_, io::Error>(msg.to_string())));
+    let stream = rx.flat_map(|line| {
+        let msgs = parse_line(&line).into_iter().map(Ok::<_, io::Error>);
+        stream::iter(msgs)
+    });
+    (Box::new(sink), Box::new(stream))
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::task::block_on;
+
+    use super::*;
+
+    #[test]
+    fn both_ways() {
+        let (mut engine, mut client) = duplex();
+        block_on(async {
+            client.sink.send(UciMessage::Uci).await.unwrap();
+            client.sink.send(UciMessage::go()).await.unwrap();
+            assert_eq!(
+                UciMessage::Uci,
+                engine.stream.next().await.unwrap().unwrap()
+            );
+            assert!(matches!(
+                engine.stream.next().await.unwrap().unwrap(),
+                UciMessage::Go { .. }
+            ));
+            engine.sink.send(UciMessage::UciOk).await.unwrap();
+            assert_eq!(
+                UciMessage::UciOk,
+                client.stream.next().await.unwrap().unwrap()
+            );
+            // closing one end ends the other's stream
+            drop(engine);
+            assert!(client.stream.next().await.is_none());
+            assert!(client.sink.send(UciMessage::IsReady).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,297 @@
+use chess::ChessMove;
+use futures::{join, StreamExt};
+use vampirc_uci::{UciFen, UciMessage, UciOptionConfig, UciSearchControl, UciTimeControl};
+
+use crate::uci::{
+    new_channel, new_try_channel, parse_go_ponder, run_loops, stdin_msg_stream, stdout_msg_sink,
+    LoopEnd, UciSender, UciSink, UciStream, UciTryReceiver,
+};
+
+/// The engine side of UCI, a method per command. [run_engine] reads commands
+/// and calls these, so an engine needn't have a message loop of its own. Any
+/// which send output get a [UciSender] to do it with, which may be kept, for
+/// sending from another thread (e.g., a search's `info` and `bestmove`).
+pub trait Engine {
+    /// For `id name`, and prefixing log lines.
+    fn name(&self) -> String;
+
+    /// For `id author`.
+    fn author(&self) -> String;
+
+    /// The options to declare in reply to `uci`, before `uciok`.
+    fn uci(&mut self) -> Vec<UciOptionConfig> {
+        Vec::new()
+    }
+
+    /// Return once ready for more commands, for `isready`. The `readyok` is
+    /// sent after.
+    fn is_ready(&mut self) {}
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String>;
+
+    fn new_game(&mut self) {}
+
+    fn position(
+        &mut self,
+        startpos: bool,
+        fen: Option<&UciFen>,
+        moves: &[ChessMove],
+    ) -> Result<(), String>;
+
+    /// Start searching the last position, and send `bestmove` once done,
+    /// whether by finishing or being stopped.
+    fn go(
+        &mut self,
+        time_control: Option<UciTimeControl>,
+        search_control: Option<UciSearchControl>,
+        sender: &UciSender,
+    );
+
+    /// `go ponder` with a clock (or other limits) after it, which
+    /// [UciMessage::Go] can't carry: `time_control` is the clock to go by once
+    /// `ponderhit` comes. By default, it's pondered on without one.
+    fn go_ponder(
+        &mut self,
+        _time_control: Option<UciTimeControl>,
+        search_control: Option<UciSearchControl>,
+        sender: &UciSender,
+    ) {
+        self.go(Some(UciTimeControl::Ponder), search_control, sender)
+    }
+
+    /// Stop searching, returning once `bestmove` has been sent.
+    fn stop(&mut self);
+
+    fn ponder_hit(&mut self);
+
+    /// The last command; the driver returns after this. Also called if the
+    /// input ends without one.
+    fn quit(&mut self) {
+        self.stop()
+    }
+
+    /// A command the parser didn't know, returning whether it was handled.
+    fn unknown(&mut self, _text: &str, _sender: &UciSender) -> bool {
+        false
+    }
+}
+
+/// Run `engine` on stdin and stdout until `quit`.
+pub async fn run_std_engine<E: Engine>(engine: &mut E) -> LoopEnd {
+    run_engine(engine, stdin_msg_stream(), stdout_msg_sink()).await
+}
+
+/// Run `engine` on the commands from `inbound`, sending its output to
+/// `outbound`, until `quit`, or either breaks.
+pub async fn run_engine<E: Engine>(
+    engine: &mut E,
+    inbound: Box<UciStream>,
+    outbound: Box<UciSink>,
+) -> LoopEnd {
+    let (itx, irx) = new_try_channel();
+    let (otx, orx) = new_channel();
+    let (end, _) = join!(
+        run_loops(inbound, itx, orx, outbound),
+        drive(engine, irx, otx)
+    );
+    end
+}
+
+async fn drive<E: Engine>(engine: &mut E, mut inbound: UciTryReceiver, sender: UciSender) {
+    let prefix = format!("[{}]", engine.name().to_uppercase());
+    while let Some(msg_r) = inbound.next().await {
+        match msg_r {
+            Ok(msg) => {
+                eprintln!("{prefix} < {msg}");
+                match dispatch(engine, msg, &sender) {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => eprintln!("{prefix} ! {e}"),
+                }
+            }
+            Err(e) => eprintln!("{prefix} ! {e}"),
+        }
+    }
+    // the GUI's gone without a word
+    engine.quit();
+}
+
+/// Hand `msg` to `engine`, returning whether to carry on, or what went wrong.
+fn dispatch<E: Engine>(
+    engine: &mut E,
+    msg: UciMessage,
+    sender: &UciSender,
+) -> Result<bool, String> {
+    // if the GUI's gone, there's no one to tell
+    let send = |msg| {
+        let _ = sender.unbounded_send(msg);
+    };
+    match msg {
+        UciMessage::Uci => {
+            send(UciMessage::id_name(&engine.name()));
+            send(UciMessage::id_author(&engine.author()));
+            for opt in engine.uci() {
+                send(UciMessage::Option(opt));
+            }
+            send(UciMessage::UciOk);
+        }
+        UciMessage::IsReady => {
+            engine.is_ready();
+            send(UciMessage::ReadyOk);
+        }
+        UciMessage::SetOption { name, value } => engine.set_option(&name, value.as_deref())?,
+        UciMessage::UciNewGame => engine.new_game(),
+        UciMessage::Position {
+            startpos,
+            fen,
+            moves,
+        } => engine
+            .position(startpos, fen.as_ref(), &moves)
+            .map_err(|e| format!("bad position: {e}"))?,
+        UciMessage::Go {
+            time_control,
+            search_control,
+        } => engine.go(time_control, search_control, sender),
+        UciMessage::Stop => engine.stop(),
+        UciMessage::PonderHit => engine.ponder_hit(),
+        UciMessage::Quit => {
+            engine.quit();
+            return Ok(false);
+        }
+        UciMessage::Unknown(text, _) => {
+            if let Some((time_control, search_control)) = parse_go_ponder(&text) {
+                engine.go_ponder(time_control, search_control, sender);
+            } else if !engine.unknown(&text, sender) {
+                return Err(format!("unknown command: {text}"));
+            }
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use async_std::io;
+    use async_std::task::block_on;
+    use futures::SinkExt;
+
+    use crate::uci::{from_reader, UciReceiver};
+
+    use super::*;
+
+    /// Plays the first move of the search moves, or says it has none.
+    #[derive(Default)]
+    struct Stub {
+        moves: usize,
+        stops: usize,
+        quit: bool,
+    }
+
+    impl Engine for Stub {
+        fn name(&self) -> String {
+            "Stub".to_string()
+        }
+
+        fn author(&self) -> String {
+            "Nobody".to_string()
+        }
+
+        fn set_option(&mut self, name: &str, _value: Option<&str>) -> Result<(), String> {
+            Err(format!("unknown option: {name}"))
+        }
+
+        fn position(
+            &mut self,
+            _startpos: bool,
+            _fen: Option<&UciFen>,
+            moves: &[ChessMove],
+        ) -> Result<(), String> {
+            self.moves = moves.len();
+            Ok(())
+        }
+
+        fn go(
+            &mut self,
+            _time_control: Option<UciTimeControl>,
+            search_control: Option<UciSearchControl>,
+            sender: &UciSender,
+        ) {
+            let m = search_control.and_then(|sc| sc.search_moves.first().copied());
+            let msg = match m {
+                Some(m) => UciMessage::best_move(m),
+                None => UciMessage::Unknown("bestmove 0000".to_string(), None),
+            };
+            sender.unbounded_send(msg).unwrap();
+        }
+
+        fn stop(&mut self) {
+            self.stops += 1;
+        }
+
+        fn ponder_hit(&mut self) {}
+
+        fn quit(&mut self) {
+            self.quit = true;
+        }
+    }
+
+    fn drain(rx: &mut UciReceiver) -> Vec<UciMessage> {
+        let mut msgs = vec![];
+        while let Ok(Some(msg)) = rx.try_next() {
+            msgs.push(msg);
+        }
+        msgs
+    }
+
+    #[test]
+    fn commands() {
+        let mut stub = Stub::default();
+        let (tx, mut rx) = new_channel();
+        assert_eq!(Ok(true), dispatch(&mut stub, UciMessage::Uci, &tx));
+        let msgs = drain(&mut rx);
+        assert_eq!(UciMessage::id_name("Stub"), msgs[0]);
+        assert_eq!(Some(&UciMessage::UciOk), msgs.last());
+        assert_eq!(Ok(true), dispatch(&mut stub, UciMessage::IsReady, &tx));
+        assert_eq!(vec![UciMessage::ReadyOk], drain(&mut rx));
+        let position = UciMessage::Position {
+            startpos: true,
+            fen: None,
+            moves: vec![ChessMove::default(); 2],
+        };
+        assert_eq!(Ok(true), dispatch(&mut stub, position, &tx));
+        assert_eq!(2, stub.moves);
+        assert_eq!(Ok(true), dispatch(&mut stub, UciMessage::Stop, &tx));
+        assert_eq!(1, stub.stops);
+        let unknown = UciMessage::Unknown("xyzzy".to_string(), None);
+        assert!(dispatch(&mut stub, unknown, &tx).is_err());
+        // what vampirc can't parse, but dispatch can
+        let text = "go ponder wtime 1000 btime 1000 searchmoves e7e5";
+        let go_ponder = UciMessage::Unknown(text.to_string(), None);
+        assert_eq!(Ok(true), dispatch(&mut stub, go_ponder, &tx));
+        let e5 = UciMessage::best_move(ChessMove::from_str("e7e5").unwrap());
+        assert_eq!(vec![e5], drain(&mut rx));
+        let option = UciMessage::SetOption {
+            name: "Hash".to_string(),
+            value: Some("1".to_string()),
+        };
+        assert!(dispatch(&mut stub, option, &tx).is_err());
+        assert_eq!(Ok(false), dispatch(&mut stub, UciMessage::Quit, &tx));
+        assert!(stub.quit);
+    }
+
+    #[test]
+    fn runs_until_quit() {
+        let input = "uci\nisready\nposition startpos moves e2e4\ngo searchmoves e7e5\nquit\n";
+        let inbound = from_reader(io::BufReader::new(io::Cursor::new(input)));
+        let (tx, _rx) = new_channel();
+        let outbound = Box::new(tx.sink_map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+        let mut stub = Stub::default();
+        let end = block_on(run_engine(&mut stub, inbound, outbound));
+        assert!(matches!(end, LoopEnd::Quit), "{end:?}");
+        assert_eq!(1, stub.moves);
+        assert!(stub.quit);
+    }
+}
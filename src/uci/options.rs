@@ -0,0 +1,417 @@
+use vampirc_uci::{UciMessage, UciOptionConfig};
+
+/// An option's value, typed by its declaration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    String(String),
+}
+
+impl OptionValue {
+    /// As the value of a `setoption`, which a button hasn't.
+    pub fn to_uci(&self) -> Option<String> {
+        match self {
+            OptionValue::Check(b) => Some(b.to_string()),
+            OptionValue::Spin(n) => Some(n.to_string()),
+            OptionValue::Combo(s) => Some(s.clone()),
+            OptionValue::Button => None,
+            OptionValue::String(s) if s.is_empty() => Some(EMPTY.to_string()),
+            OptionValue::String(s) => Some(s.clone()),
+        }
+    }
+
+    pub fn as_check(&self) -> Option<bool> {
+        match self {
+            OptionValue::Check(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_spin(&self) -> Option<i64> {
+        match self {
+            OptionValue::Spin(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value of a string or combo option.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            OptionValue::String(s) | OptionValue::Combo(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Called with `T`, the thing the options configure, and an option's new
+/// value, each time it's set.
+pub type OnChange<T> = Box<dyn FnMut(&mut T, &OptionValue) -> Result<(), String> + Send>;
+
+struct Declared<T> {
+    config: UciOptionConfig,
+    value: OptionValue,
+    on_change: Option<OnChange<T>>,
+}
+
+/// The options an engine declares, with their current values. An engine
+/// declares its own, with an [on_change] callback for each to apply it to the
+/// engine (`T`), and sends [Options::uci_options] on `uci`, then hands each
+/// `setoption` to [Options::set]. A GUI (like birch) can [declare] the options
+/// an engine sends, and [Options::parse] values before setting them. Names
+/// match without regard to case, as UCI has it.
+///
+/// [on_change]: Options::on_change
+/// [declare]: Options::declare
+pub struct Options<T = ()> {
+    declared: Vec<Declared<T>>,
+}
+
+impl<T> Default for Options<T> {
+    fn default() -> Self {
+        Options {
+            declared: Vec::new(),
+        }
+    }
+}
+
+/// The name an option's declared with.
+pub fn name_of(config: &UciOptionConfig) -> &str {
+    match config {
+        UciOptionConfig::Check { name, .. }
+        | UciOptionConfig::Spin { name, .. }
+        | UciOptionConfig::Combo { name, .. }
+        | UciOptionConfig::Button { name }
+        | UciOptionConfig::String { name, .. } => name,
+    }
+}
+
+/// UCI's way of saying a string is empty, since `value` with nothing after it
+/// is ambiguous.
+const EMPTY: &str = "<empty>";
+
+fn initial_value(config: &UciOptionConfig) -> OptionValue {
+    match config {
+        UciOptionConfig::Check { default, .. } => OptionValue::Check(default.unwrap_or(false)),
+        UciOptionConfig::Spin { default, min, .. } => {
+            OptionValue::Spin(default.or(*min).unwrap_or(0))
+        }
+        UciOptionConfig::Combo { default, var, .. } => OptionValue::Combo(
+            default
+                .clone()
+                .or_else(|| var.first().cloned())
+                .unwrap_or_default(),
+        ),
+        UciOptionConfig::Button { .. } => OptionValue::Button,
+        UciOptionConfig::String { default, .. } => OptionValue::String(match default {
+            Some(s) if s != EMPTY => s.clone(),
+            _ => String::new(),
+        }),
+    }
+}
+
+impl<T> Options<T> {
+    pub fn new() -> Options<T> {
+        Options::default()
+    }
+
+    pub fn check(mut self, name: &str, default: bool) -> Options<T> {
+        self.declare(UciOptionConfig::Check {
+            name: name.to_string(),
+            default: Some(default),
+        });
+        self
+    }
+
+    pub fn spin(mut self, name: &str, default: i64, min: i64, max: i64) -> Options<T> {
+        self.declare(UciOptionConfig::Spin {
+            name: name.to_string(),
+            default: Some(default),
+            min: Some(min),
+            max: Some(max),
+        });
+        self
+    }
+
+    pub fn combo(mut self, name: &str, default: &str, vars: &[&str]) -> Options<T> {
+        self.declare(UciOptionConfig::Combo {
+            name: name.to_string(),
+            default: Some(default.to_string()),
+            var: vars.iter().map(|v| v.to_string()).collect(),
+        });
+        self
+    }
+
+    pub fn button(mut self, name: &str) -> Options<T> {
+        self.declare(UciOptionConfig::Button {
+            name: name.to_string(),
+        });
+        self
+    }
+
+    pub fn string(mut self, name: &str, default: &str) -> Options<T> {
+        self.declare(UciOptionConfig::String {
+            name: name.to_string(),
+            default: Some(if default.is_empty() { EMPTY } else { default }.to_string()),
+        });
+        self
+    }
+
+    /// Call `on_change` whenever option `name` is set, once its value's been
+    /// checked. It's an error if there's no such option.
+    pub fn on_change<F>(mut self, name: &str, on_change: F) -> Result<Options<T>, String>
+    where
+        F: FnMut(&mut T, &OptionValue) -> Result<(), String> + Send + 'static,
+    {
+        let Some(d) = self.find_mut(name) else {
+            return Err(format!("Option {name} isn't declared"));
+        };
+        d.on_change = Some(Box::new(on_change));
+        Ok(self)
+    }
+
+    /// Add an option, at its default, replacing any of the same name (and its
+    /// [Options::on_change]).
+    pub fn declare(&mut self, config: UciOptionConfig) {
+        let declared = Declared {
+            value: initial_value(&config),
+            config,
+            on_change: None,
+        };
+        match self.find_mut(name_of(&declared.config)) {
+            Some(d) => *d = declared,
+            None => self.declared.push(declared),
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&Declared<T>> {
+        let name = name.trim();
+        self.declared
+            .iter()
+            .find(|d| name_of(&d.config).eq_ignore_ascii_case(name))
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut Declared<T>> {
+        let name = name.trim();
+        self.declared
+            .iter_mut()
+            .find(|d| name_of(&d.config).eq_ignore_ascii_case(name))
+    }
+
+    /// The declarations, in the order they were made.
+    pub fn uci_options(&self) -> Vec<UciOptionConfig> {
+        self.declared.iter().map(|d| d.config.clone()).collect()
+    }
+
+    /// The `option` messages to send on `uci`.
+    pub fn messages(&self) -> Vec<UciMessage> {
+        self.uci_options()
+            .into_iter()
+            .map(UciMessage::Option)
+            .collect()
+    }
+
+    /// Parse a `setoption` value for option `name`, without setting it.
+    /// Returns `None` if there's no such option.
+    pub fn parse(&self, name: &str, value: Option<&str>) -> Result<Option<OptionValue>, String> {
+        let Some(d) = self.find(name) else {
+            return Ok(None);
+        };
+        let name = name_of(&d.config);
+        let value = value.map(str::trim);
+        let parsed = match &d.config {
+            UciOptionConfig::Check { .. } => match value {
+                Some(v) if v.eq_ignore_ascii_case("true") => OptionValue::Check(true),
+                Some(v) if v.eq_ignore_ascii_case("false") => OptionValue::Check(false),
+                v => return Err(format!("Option {name} must be true or false, not {v:?}")),
+            },
+            UciOptionConfig::Spin { min, max, .. } => {
+                let min = min.unwrap_or(i64::MIN);
+                let max = max.unwrap_or(i64::MAX);
+                match value.and_then(|v| v.parse::<i64>().ok()) {
+                    Some(n) if (min..=max).contains(&n) => OptionValue::Spin(n),
+                    _ => {
+                        return Err(format!(
+                            "Option {name} must be {min} to {max}, not {value:?}"
+                        ));
+                    }
+                }
+            }
+            UciOptionConfig::Combo { var, .. } => {
+                match var
+                    .iter()
+                    .find(|v| value.is_some_and(|s| v.eq_ignore_ascii_case(s)))
+                {
+                    Some(v) => OptionValue::Combo(v.clone()),
+                    None => {
+                        let vars = var.join(", ");
+                        return Err(format!(
+                            "Option {name} must be one of {vars}, not {value:?}"
+                        ));
+                    }
+                }
+            }
+            UciOptionConfig::Button { .. } => OptionValue::Button,
+            UciOptionConfig::String { .. } => OptionValue::String(match value {
+                None | Some(EMPTY) => String::new(),
+                Some(v) => v.to_string(),
+            }),
+        };
+        Ok(Some(parsed))
+    }
+
+    /// Apply a UCI `setoption` to `target`, returning whether the option was
+    /// one of these. A bad value changes nothing, but if the option's
+    /// [Options::on_change] fails, the value is kept, and its error returned.
+    pub fn set(&mut self, target: &mut T, name: &str, value: Option<&str>) -> Result<bool, String> {
+        let Some(parsed) = self.parse(name, value)? else {
+            return Ok(false);
+        };
+        let d = self
+            .find_mut(name)
+            .expect("Parsed option should be declared");
+        d.value = parsed;
+        if let Some(on_change) = d.on_change.as_mut() {
+            on_change(target, &d.value)?;
+        }
+        Ok(true)
+    }
+
+    /// The current value of option `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        self.find(name).map(|d| &d.value)
+    }
+
+    pub fn get_check(&self, name: &str) -> Option<bool> {
+        self.get(name)?.as_check()
+    }
+
+    pub fn get_spin(&self, name: &str) -> Option<i64> {
+        self.get(name)?.as_spin()
+    }
+
+    /// The value of a string or combo option.
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        self.get(name)?.as_str()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn options<T>() -> Options<T> {
+        Options::new()
+            .check("Ponder", false)
+            .spin("Hash", 16, 1, 1024)
+            .combo("Style", "Normal", &["Solid", "Normal", "Risky"])
+            .button("Clear Hash")
+            .string("SyzygyPath", "")
+    }
+
+    #[test]
+    fn declarations() {
+        let opts = options::<()>();
+        let configs = opts.uci_options();
+        assert_eq!(5, configs.len());
+        assert_eq!("Hash", name_of(&configs[1]));
+        assert_eq!(UciMessage::Option(configs[4].clone()), opts.messages()[4]);
+        assert_eq!(Some(false), opts.get_check("ponder"));
+        assert_eq!(Some(16), opts.get_spin("Hash"));
+        assert_eq!(Some("Normal"), opts.get_string("Style"));
+        assert_eq!(Some(""), opts.get_string("SyzygyPath"));
+        assert_eq!(None, opts.get_spin("Ponder"));
+        assert_eq!(None, opts.get("Threads"));
+    }
+
+    #[test]
+    fn parsing() {
+        let opts = options::<()>();
+        assert_eq!(
+            Ok(Some(OptionValue::Spin(64))),
+            opts.parse("hash", Some(" 64"))
+        );
+        assert!(opts.parse("Hash", Some("0")).is_err());
+        assert!(opts.parse("Hash", Some("lots")).is_err());
+        assert!(opts.parse("Hash", None).is_err());
+        assert_eq!(
+            Ok(Some(OptionValue::Check(true))),
+            opts.parse("Ponder", Some("TRUE"))
+        );
+        assert!(opts.parse("Ponder", Some("yes")).is_err());
+        let risky = OptionValue::Combo("Risky".to_string());
+        assert_eq!(Ok(Some(risky)), opts.parse("Style", Some("risky")));
+        assert!(opts.parse("Style", Some("Wild")).is_err());
+        assert_eq!(
+            Ok(Some(OptionValue::Button)),
+            opts.parse("Clear Hash", None)
+        );
+        let empty = OptionValue::String(String::new());
+        assert_eq!(Ok(Some(empty)), opts.parse("SyzygyPath", Some("<empty>")));
+        assert_eq!(Ok(None), opts.parse("Threads", Some("2")));
+        // and back again
+        for config in opts.uci_options() {
+            let name = name_of(&config);
+            let value = opts.get(name).unwrap().to_uci();
+            assert_eq!(
+                opts.get(name),
+                opts.parse(name, value.as_deref()).unwrap().as_ref()
+            );
+        }
+    }
+
+    #[test]
+    fn setting() {
+        let mut opts = options::<()>();
+        assert_eq!(Ok(true), opts.set(&mut (), "Hash", Some("128")));
+        assert_eq!(Some(128), opts.get_spin("Hash"));
+        // a bad value changes nothing
+        assert!(opts.set(&mut (), "Hash", Some("4096")).is_err());
+        assert_eq!(Some(128), opts.get_spin("Hash"));
+        assert_eq!(Ok(false), opts.set(&mut (), "Threads", Some("2")));
+    }
+
+    #[test]
+    fn on_change() {
+        let mut opts = options::<Vec<OptionValue>>()
+            .on_change("hash", |log, v| {
+                log.push(v.clone());
+                Ok(())
+            })
+            .unwrap()
+            .on_change("SyzygyPath", |_, v| Err(format!("can't load {v:?}")))
+            .unwrap();
+        let mut log = vec![];
+        assert_eq!(Ok(true), opts.set(&mut log, "Hash", Some("128")));
+        // not for a bad value, nor another option
+        assert!(opts.set(&mut log, "Hash", Some("4096")).is_err());
+        assert_eq!(Ok(true), opts.set(&mut log, "Ponder", Some("true")));
+        assert_eq!(vec![OptionValue::Spin(128)], log);
+        // a failed change is reported, but kept
+        assert!(opts.set(&mut log, "SyzygyPath", Some("/tb")).is_err());
+        assert_eq!(Some("/tb"), opts.get_string("SyzygyPath"));
+        assert!(options::<()>().on_change("Threads", |_, _| Ok(())).is_err());
+    }
+
+    #[test]
+    fn declared_by_engine() {
+        let mut opts: Options = Options::new();
+        opts.declare(UciOptionConfig::Spin {
+            name: "Threads".to_string(),
+            default: Some(1),
+            min: Some(1),
+            max: Some(8),
+        });
+        assert_eq!(Some(1), opts.get_spin("Threads"));
+        assert!(opts.parse("Threads", Some("9")).is_err());
+        // redeclaring replaces
+        opts.declare(UciOptionConfig::Check {
+            name: "threads".to_string(),
+            default: Some(true),
+        });
+        assert_eq!(1, opts.uci_options().len());
+        assert_eq!(Some(true), opts.get_check("Threads"));
+    }
+}
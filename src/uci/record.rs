@@ -0,0 +1,350 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_std::future::ready;
+use async_std::io;
+use async_std::task::sleep;
+use futures::{SinkExt, StreamExt};
+use vampirc_uci::UciMessage;
+
+use crate::uci::client::Client;
+use crate::uci::duplex::Pipe;
+use crate::uci::parse_line;
+
+/*
+A session's recorded a message per line, as the seconds since it began, `>`
+or `<` for the GUI's messages and the engine's, and the message itself:
+
+    0.000012 > uci
+    0.000873 < id name Rachel
+ */
+
+/// Which way a recorded message went.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// From the GUI, to the engine.
+    ToEngine,
+    /// From the engine, to the GUI.
+    FromEngine,
+}
+
+impl Direction {
+    fn other(self) -> Direction {
+        match self {
+            Direction::ToEngine => Direction::FromEngine,
+            Direction::FromEngine => Direction::ToEngine,
+        }
+    }
+}
+
+/// A message of a session, and when it passed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Since the session began.
+    pub at: Duration,
+    pub direction: Direction,
+    pub msg: UciMessage,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            Direction::ToEngine => '>',
+            Direction::FromEngine => '<',
+        };
+        let msg = self.msg.to_string();
+        write!(f, "{:.6} {arrow} {}", self.at.as_secs_f64(), msg.trim())
+    }
+}
+
+impl FromStr for Entry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, ' ');
+        let at = parts
+            .next()
+            .and_then(|t| t.parse::<f64>().ok())
+            .filter(|t| t.is_finite() && *t >= 0.0)
+            .ok_or_else(|| format!("No time in '{s}'"))?;
+        let direction = match parts.next() {
+            Some(">") => Direction::ToEngine,
+            Some("<") => Direction::FromEngine,
+            _ => return Err(format!("No direction in '{s}'")),
+        };
+        let msg = parts
+            .next()
+            .and_then(|text| parse_line(text).into_iter().next())
+            .ok_or_else(|| format!("No message in '{s}'"))?;
+        Ok(Entry {
+            at: Duration::from_secs_f64(at),
+            direction,
+            msg,
+        })
+    }
+}
+
+/// Writes a session's messages down as they pass, from either end of it.
+/// Clones write to the same place, on the same clock.
+#[derive(Clone)]
+pub struct Recorder {
+    start: Instant,
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Recorder {
+    pub fn new<W>(out: W) -> Recorder
+    where
+        W: Write + Send + 'static,
+    {
+        Recorder {
+            start: Instant::now(),
+            out: Arc::new(Mutex::new(Box::new(out))),
+        }
+    }
+
+    /// Record to a new file at `path`, a line at a time, so a crash loses
+    /// nothing said before it.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        Ok(Recorder::new(LineWriter::new(File::create(path)?)))
+    }
+
+    /// Write `msg` down, as having just gone `direction`. Failing to is
+    /// reported, but doesn't get in the session's way.
+    pub fn record(&self, direction: Direction, msg: &UciMessage) {
+        let entry = Entry {
+            at: self.start.elapsed(),
+            direction,
+            msg: msg.clone(),
+        };
+        let mut out = self
+            .out
+            .lock()
+            .expect("Recorder lock shouldn't be poisoned");
+        if let Err(e) = writeln!(out, "{entry}") {
+            eprintln!("[RECORD] ! {e}");
+        }
+    }
+
+    /// Record what passes through `pipe`: what's sent into its sink going
+    /// `sent`, and what comes out of its stream the other way.
+    pub fn tap(&self, pipe: Pipe, sent: Direction) -> Pipe {
+        let out = self.clone();
+        let sink = pipe.sink.with(move |msg: UciMessage| {
+            out.record(sent, &msg);
+            ready(Ok::<_, io::Error>(msg))
+        });
+        let inb = self.clone();
+        let stream = pipe.stream.inspect(move |r| {
+            if let Ok(msg) = r {
+                inb.record(sent.other(), msg);
+            }
+        });
+        Pipe {
+            stream: Box::new(stream),
+            sink: Box::new(sink),
+        }
+    }
+}
+
+/// A recorded session's entries, in order.
+pub fn read_session<R: BufRead>(reader: R) -> Result<Vec<Entry>, String> {
+    let mut entries = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(line.parse().map_err(|e| format!("line {}: {e}", i + 1))?);
+    }
+    Ok(entries)
+}
+
+/// Where a replayed engine said other than it had.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    /// Of the recorded message, in the session's entries.
+    pub index: usize,
+    pub expected: UciMessage,
+    /// None if the engine said nothing in time, or hung up.
+    pub actual: Option<UciMessage>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = self.expected.to_string();
+        let expected = expected.trim();
+        match &self.actual {
+            Some(msg) => {
+                let actual = msg.to_string();
+                let actual = actual.trim();
+                write!(f, "#{}: expected '{expected}', got '{actual}'", self.index)
+            }
+            None => write!(f, "#{}: expected '{expected}', got nothing", self.index),
+        }
+    }
+}
+
+/// Whether a message of the engine's is compared on replay. `info` isn't, as
+/// its times and node counts vary from run to run.
+fn compared(msg: &UciMessage) -> bool {
+    !matches!(msg, UciMessage::Info(_))
+}
+
+/// Send `session`'s GUI messages to the engine at the end of `client`, each
+/// no sooner than it was recorded, and compare what the engine says back with
+/// what it said then, waiting up to `within` for each. Once the engine hangs
+/// up, everything it was still to say diverges.
+pub async fn replay(session: &[Entry], client: &mut Client, within: Duration) -> Vec<Divergence> {
+    let start = Instant::now();
+    let mut hung_up = false;
+    let mut divergences = vec![];
+    for (index, entry) in session.iter().enumerate() {
+        match entry.direction {
+            Direction::ToEngine if !hung_up => {
+                sleep(entry.at.saturating_sub(start.elapsed())).await;
+                hung_up = client.send(entry.msg.clone()).await.is_err();
+            }
+            Direction::FromEngine if compared(&entry.msg) => {
+                let actual = if hung_up {
+                    None
+                } else {
+                    match client.recv_until(within, compared).await {
+                        Ok(mut msgs) => msgs.pop(),
+                        Err(e) => {
+                            hung_up = e.kind() == io::ErrorKind::UnexpectedEof;
+                            None
+                        }
+                    }
+                };
+                if actual.as_ref() != Some(&entry.msg) {
+                    divergences.push(Divergence {
+                        index,
+                        expected: entry.msg.clone(),
+                        actual,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    divergences
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use async_std::task::block_on;
+
+    use crate::rachel::engine::Rachel;
+
+    use super::*;
+
+    const WITHIN: Duration = Duration::from_secs(10);
+
+    /// Somewhere to record to, to read back after.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn session(text: &str) -> Vec<Entry> {
+        read_session(Cursor::new(text)).unwrap()
+    }
+
+    #[test]
+    fn entries() {
+        for line in ["0.000012 > uci", "1.500000 < bestmove e2e4 ponder e7e5"] {
+            assert_eq!(line, line.parse::<Entry>().unwrap().to_string());
+        }
+        let entry: Entry = "2.25 > isready".parse().unwrap();
+        assert_eq!(Duration::from_millis(2250), entry.at);
+        assert_eq!(Direction::ToEngine, entry.direction);
+        assert_eq!(UciMessage::IsReady, entry.msg);
+        assert!("uci".parse::<Entry>().is_err());
+        assert!("0.1 = uci".parse::<Entry>().is_err());
+        assert!("0.1 >".parse::<Entry>().is_err());
+        assert!(read_session(Cursor::new("0.1 > uci\n\nnonsense\n"))
+            .unwrap_err()
+            .starts_with("line 3"));
+    }
+
+    #[test]
+    fn records() {
+        let shared = Shared::default();
+        let recorder = Recorder::new(shared.clone());
+        block_on(async {
+            let mut client = Client::builtin(Rachel::new());
+            client.record(&recorder);
+            client.uci(WITHIN).await.unwrap();
+            client.is_ready(WITHIN).await.unwrap();
+            client.send(UciMessage::Quit).await.unwrap();
+            client.close(WITHIN).await.unwrap();
+        });
+        let text = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+        let entries = session(&text);
+        assert_eq!(UciMessage::Uci, entries[0].msg);
+        assert_eq!(Direction::ToEngine, entries[0].direction);
+        assert!(entries.windows(2).all(|w| w[0].at <= w[1].at));
+        let from_engine: Vec<_> = entries
+            .iter()
+            .filter(|e| e.direction == Direction::FromEngine)
+            .map(|e| &e.msg)
+            .collect();
+        assert!(from_engine.contains(&&UciMessage::UciOk));
+        assert_eq!(Some(&&UciMessage::ReadyOk), from_engine.last());
+        assert_eq!(Some(&UciMessage::Quit), entries.last().map(|e| &e.msg));
+    }
+
+    #[test]
+    fn replays() {
+        // depth 1 from the start is deterministic, though its info isn't
+        let recorded = "\
+            0.0 > isready\n\
+            0.0 < readyok\n\
+            0.0 > position startpos\n\
+            0.0 > go depth 1\n\
+            0.0 < info depth 1 nodes 999999\n";
+        let reply = block_on(async {
+            let mut client = Client::builtin(Rachel::new());
+            let divergences = replay(&session(recorded), &mut client, WITHIN).await;
+            assert_eq!(Vec::<Divergence>::new(), divergences);
+            let reply = client.best_move(WITHIN).await.unwrap();
+            client.close(WITHIN).await.unwrap();
+            reply
+        });
+        let best_move = reply.best_move.unwrap();
+
+        // the same, but expecting another move
+        let other = if best_move.to_string() == "a2a3" {
+            "h2h3"
+        } else {
+            "a2a3"
+        };
+        let recorded = format!("{recorded}0.0 < bestmove {other}\n0.0 > quit\n");
+        let mut client = Client::builtin(Rachel::new());
+        let divergences = block_on(replay(&session(&recorded), &mut client, WITHIN));
+        assert_eq!(1, divergences.len());
+        assert_eq!(5, divergences[0].index);
+        let actual = UciMessage::BestMove {
+            best_move,
+            ponder: reply.ponder,
+        };
+        assert_eq!(Some(actual), divergences[0].actual);
+        block_on(client.close(WITHIN)).unwrap();
+    }
+}
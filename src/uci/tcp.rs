@@ -0,0 +1,83 @@
+use std::thread;
+
+use async_std::future::ready;
+use async_std::io;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task::block_on;
+use futures::{AsyncWriteExt, SinkExt, StreamExt};
+use vampirc_uci::{ByteVecUciMessage, UciMessage};
+
+use crate::uci::duplex::Pipe;
+use crate::uci::engine::{run_engine, Engine};
+use crate::uci::from_reader;
+
+/// Both directions of a TCP connection, a line per message, as though it were
+/// a process's stdin and stdout.
+pub fn tcp_pipe(stream: TcpStream) -> Pipe {
+    let sink = stream
+        .clone()
+        .into_sink()
+        .with(|msg: UciMessage| ready(Ok::<_, io::Error>(ByteVecUciMessage::from(msg))));
+    Pipe {
+        stream: from_reader(io::BufReader::new(stream)),
+        sink: Box::new(sink),
+    }
+}
+
+/// Accept connections on `listener` forever, each a session with a fresh
+/// engine from `new_engine`, on a thread of its own. A session ends with
+/// `quit`, or when the client disconnects.
+pub async fn serve<E, F>(listener: TcpListener, new_engine: F) -> io::Result<()>
+where
+    E: Engine + Send + 'static,
+    F: Fn() -> E,
+{
+    eprintln!("[TCP] listening on {}", listener.local_addr()?);
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let (stream, peer) = match stream.and_then(|s| s.peer_addr().map(|a| (s, a))) {
+            Ok(sp) => sp,
+            Err(e) => {
+                eprintln!("[TCP] ! {e}");
+                continue;
+            }
+        };
+        let mut engine = new_engine();
+        thread::spawn(move || {
+            eprintln!("[TCP] session with {peer} started");
+            let pipe = tcp_pipe(stream);
+            let end = block_on(run_engine(&mut engine, pipe.stream, pipe.sink));
+            eprintln!("[TCP] session with {peer} ended: {end:?}");
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rachel::engine::Rachel;
+
+    use super::*;
+
+    #[test]
+    fn sessions() {
+        let listener = block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || block_on(serve(listener, Rachel::new)));
+        block_on(async {
+            // two sessions at once, each with its own engine
+            let mut first = tcp_pipe(TcpStream::connect(addr).await.unwrap());
+            let mut second = tcp_pipe(TcpStream::connect(addr).await.unwrap());
+            for pipe in [&mut first, &mut second] {
+                pipe.sink.send(UciMessage::IsReady).await.unwrap();
+                let reply = pipe.stream.next().await.unwrap().unwrap();
+                assert_eq!(UciMessage::ReadyOk, reply);
+            }
+            first.sink.send(UciMessage::Quit).await.unwrap();
+            assert!(first.stream.next().await.is_none());
+            second.sink.send(UciMessage::Uci).await.unwrap();
+            let reply = second.stream.next().await.unwrap().unwrap();
+            assert_eq!(UciMessage::id_name("Rachel"), reply);
+        });
+    }
+}